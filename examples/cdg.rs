@@ -0,0 +1,24 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::control_dependence::ControlDependence;
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        println!("digraph {} {{", func.name);
+        println!("  node [shape=box];");
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let cd = ControlDependence::build(&cfg);
+        let names = cfg.reverse_postorder();
+        for name in &names {
+            println!("  {};", name);
+        }
+        for name in &names {
+            let mut dependents: Vec<_> = cd.dependents(name).cloned().collect();
+            dependents.sort();
+            println!("  {} -> {{ {} }}", name, dependents.join(", "));
+        }
+        println!("}}");
+    }
+}
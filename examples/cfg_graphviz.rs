@@ -7,16 +7,19 @@ fn main() {
     for func in p.functions {
         println!("digraph {} {{", func.name);
         println!("  node [shape=box];");
+        if func.instrs.is_empty() {
+            println!("}}");
+            continue;
+        }
         let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
-        let mut names: Vec<String> = cfg.nodes.keys().cloned().collect();
-        names.sort();
-        for name in &names {
+        for name in &cfg.order {
             println!("  {};", name);
         }
-        for name in names {
-            let node = cfg.nodes.get(&name).unwrap();
-            let next = node.next.join(",");
-            println!("  {} -> {{ {} }}", name, next);
+        for name in &cfg.order {
+            let node = cfg.nodes.get(name).unwrap();
+            for (nx, kind) in node.edges() {
+                println!("  {} -> {} [label=\"{}\"];", name, nx, kind);
+            }
         }
         println!("}}");
     }
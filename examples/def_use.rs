@@ -0,0 +1,46 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::bril_text::fmt_code;
+use cs6120::cfg::{self, InstructionId};
+use cs6120::data_flow_framework::{DataFlowAnalysis, ReachingDefinition};
+use cs6120::def_use::{DefUse, Use};
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        println!("{}:", func.name);
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let init = ReachingDefinition::init(&cfg, &func.args);
+        let result = ReachingDefinition::drive(&cfg, init);
+        let du = DefUse::build(&cfg, &result);
+
+        for (name, _) in result.iter() {
+            for (i, code) in cfg.nodes[name].block.iter().enumerate() {
+                let id = InstructionId::new(name.clone(), i);
+                let mut uses: Vec<_> = du.uses(&id).map(|u| format!("{}", u.id)).collect();
+                uses.sort();
+                uses.dedup();
+                println!("  {}: {}", id, fmt_code(code));
+                let mut args = match code {
+                    Code::Instruction(Instruction::Value { args, .. })
+                    | Code::Instruction(Instruction::Effect { args, .. }) => args.clone(),
+                    _ => Vec::new(),
+                };
+                args.sort();
+                args.dedup();
+                for var in args {
+                    let u = Use {
+                        id: id.clone(),
+                        var,
+                    };
+                    let mut defs: Vec<_> = du.defs(&u).map(|d| format!("{:?}", d)).collect();
+                    defs.sort();
+                    println!("    `{}` defined by: {}", u.var, defs.join(", "));
+                }
+                if !uses.is_empty() {
+                    println!("    used by: {}", uses.join(", "));
+                }
+            }
+        }
+    }
+}
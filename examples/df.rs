@@ -1,25 +1,111 @@
 use bril_rs::*;
 use cs6120::basic_block;
-use cs6120::cfg;
-use cs6120::data_flow_framework::{DataFlowAnalysis, DataFlowAnalysisBase, ReachingDefinition};
+use cs6120::bril_text::fmt_code;
+use cs6120::cfg::{self, Cfg, InstructionId};
+use cs6120::data_flow_framework::{
+    AnalysisResult, AvailableExpressions, ConstValue, ConstantPropagation, DataFlowAnalysis,
+    DataFlowAnalysisBase, Definition, LiveVariables, ReachingDefinition,
+};
+use cs6120::lattice::{Lattice, MaySet, MustSet};
 
-use std::collections::HashSet;
+use std::fmt::Debug;
+
+fn print_result<S: Debug>(result: &AnalysisResult<S>) {
+    for (name, (ent, out)) in result.iter() {
+        println!("{}:", name);
+        println!("  in: {:?}", ent);
+        println!(" out: {:?}", out);
+    }
+    eprintln!("converged after {} iterations", result.iterations);
+}
+
+/// Show the facts before and after each instruction.
+fn print_instruction_results<A: DataFlowAnalysis>(cfg: &Cfg, result: &AnalysisResult<A::Set>)
+where
+    A::Set: Debug,
+{
+    let facts = A::instruction_results(cfg, result);
+    println!("instructions:");
+    for (name, _) in result.iter() {
+        for (i, code) in cfg.nodes[name].block.iter().enumerate() {
+            let id = InstructionId::new(name.clone(), i);
+            if let Some((before, after)) = facts.get(&id) {
+                println!("  {}: {}", id, fmt_code(code));
+                println!("    before: {:?}", before);
+                println!("     after: {:?}", after);
+            }
+        }
+    }
+}
+
+/// Show each definition appearing in `result` next to its instruction.
+fn print_definitions(
+    cfg: &Cfg,
+    result: &AnalysisResult<<ReachingDefinition as DataFlowAnalysisBase>::Set>,
+) {
+    let order: Vec<_> = result.iter().map(|(name, _)| name.clone()).collect();
+    let mut defs: Vec<InstructionId> = result
+        .iter()
+        .flat_map(|(_, (_, out))| out.iter())
+        .filter_map(|(_, defs)| defs.elements())
+        .flatten()
+        .filter_map(|def| match def {
+            Definition::Instruction(id) => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    defs.sort_by_key(|id| (order.iter().position(|b| b == id.block()), id.index()));
+    defs.dedup();
+    println!("definitions:");
+    for id in defs {
+        println!("  {}: {}", id, fmt_code(cfg.code(&id).unwrap()));
+    }
+
+    for (id, var) in ReachingDefinition::undefined_uses(cfg, result) {
+        println!(
+            "warning: `{}` may be undefined at {}: {}",
+            var,
+            id,
+            fmt_code(cfg.code(&id).unwrap())
+        );
+    }
+}
 
 fn main() {
+    // usage: df [reaching|live|const|avail]
+    let analysis = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "reaching".to_owned());
     let p = load_program();
     for func in p.functions {
         let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
-        let mut args = <ReachingDefinition as DataFlowAnalysisBase>::Set::new();
-
-        for arg in func.args.iter() {
-            args.insert(arg.name.clone(), HashSet::new());
-        }
-        let result = ReachingDefinition::drive(&cfg, args);
-        for name in cfg.nodes.keys() {
-            let (ent, out) = result.get(name).unwrap();
-            println!("{}:", name);
-            println!("  in: {:?}", ent);
-            println!(" out: {:?}", out);
+        match analysis.as_str() {
+            "reaching" => {
+                let init = ReachingDefinition::init(&cfg, &func.args);
+                let result = ReachingDefinition::drive(&cfg, init);
+                print_result(&result);
+                print_definitions(&cfg, &result);
+            }
+            "live" => {
+                let result = LiveVariables::drive(&cfg, MaySet::top());
+                print_result(&result);
+                print_instruction_results::<LiveVariables>(&cfg, &result);
+            }
+            "const" => {
+                let args = func
+                    .args
+                    .iter()
+                    .map(|arg| (arg.name.clone(), ConstValue::Bottom))
+                    .collect();
+                let result = ConstantPropagation::drive(&cfg, args);
+                print_result(&result);
+            }
+            "avail" => {
+                // nothing is available at the function entry
+                let result = AvailableExpressions::drive(&cfg, MustSet::bottom());
+                print_result(&result);
+            }
+            _ => panic!("unknown analysis: {}", analysis),
         }
     }
 }
@@ -0,0 +1,23 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::dominance::Dominators;
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        println!("{}:", func.name);
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let dom = Dominators::compute(&cfg);
+        for name in cfg.reverse_postorder() {
+            let mut doms: Vec<_> = dom.dominators(&name).cloned().collect();
+            doms.sort();
+            println!(
+                "  {}: idom = {}, dom = {{{}}}",
+                name,
+                dom.idom(&name).unwrap_or("-"),
+                doms.join(", ")
+            );
+        }
+    }
+}
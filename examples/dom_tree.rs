@@ -0,0 +1,31 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::dominance::DominatorTree;
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        println!("digraph {} {{", func.name);
+        println!("  node [shape=box];");
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let tree = DominatorTree::new(&cfg);
+        let frontiers = tree.frontiers(&cfg);
+        let names = tree.preorder();
+        for name in &names {
+            let mut df: Vec<_> = frontiers[name].iter().cloned().collect();
+            df.sort();
+            println!(
+                "  {} [label=\"{}\\nDF: {{{}}}\"];",
+                name,
+                name,
+                df.join(", ")
+            );
+        }
+        for name in &names {
+            let children = tree.children(name).join(", ");
+            println!("  {} -> {{ {} }}", name, children);
+        }
+        println!("}}");
+    }
+}
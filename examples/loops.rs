@@ -0,0 +1,27 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::loops::LoopForest;
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        println!("{}:", func.name);
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let forest = LoopForest::build(&cfg);
+        for lp in forest.loops.iter() {
+            let mut body: Vec<_> = lp.body.iter().cloned().collect();
+            body.sort();
+            let exits: Vec<_> = lp
+                .exits
+                .iter()
+                .map(|(from, to)| format!("{}->{}", from, to))
+                .collect();
+            let indent = "  ".repeat(lp.depth);
+            println!("{}loop {} (depth {}):", indent, lp.header, lp.depth);
+            println!("{}  body: {}", indent, body.join(", "));
+            println!("{}  latches: {}", indent, lp.latches.join(", "));
+            println!("{}  exits: {}", indent, exits.join(", "));
+        }
+    }
+}
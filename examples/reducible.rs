@@ -0,0 +1,29 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+
+fn main() {
+    let p = load_program();
+    for func in p.functions {
+        let mut cfg = cfg::Cfg::build(&basic_block::basic_blocks(&func.instrs));
+        let regions = cfg.irreducible_regions();
+        if regions.is_empty() {
+            println!("{}: reducible", func.name);
+            continue;
+        }
+        for region in regions {
+            println!(
+                "{}: irreducible region {{{}}}",
+                func.name,
+                region.join(", ")
+            );
+        }
+
+        cfg.make_reducible();
+        println!("after node splitting:");
+        for name in cfg.reverse_postorder() {
+            println!("  {} -> {{ {} }}", name, cfg.nodes[&name].next.join(", "));
+        }
+        assert!(cfg.is_reducible());
+    }
+}
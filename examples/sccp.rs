@@ -0,0 +1,24 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::ssa::SsaMode;
+use cs6120::ssa_verify;
+
+fn main() {
+    let mut p = load_program();
+    for f in p.functions.iter_mut() {
+        if f.instrs.is_empty() {
+            continue;
+        }
+        let mut cfg = cfg::Cfg::build(&basic_block::basic_blocks(&f.instrs));
+        cfg.to_ssa(&f.args, SsaMode::Pruned);
+        cfg.sccp(&f.args);
+        let errors = ssa_verify::verify(&cfg, &f.args);
+        for e in errors.iter() {
+            eprintln!("{}: {}", f.name, e);
+        }
+        assert!(errors.is_empty(), "sccp produced invalid SSA");
+        f.instrs = cfg.to_instrs();
+    }
+    output_program(&p);
+}
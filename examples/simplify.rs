@@ -0,0 +1,16 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+
+fn main() {
+    let mut p = load_program();
+    for f in p.functions.iter_mut() {
+        if f.instrs.is_empty() {
+            continue;
+        }
+        let mut cfg = cfg::Cfg::build(&basic_block::basic_blocks(&f.instrs));
+        cfg.simplify();
+        f.instrs = cfg.to_instrs();
+    }
+    output_program(&p);
+}
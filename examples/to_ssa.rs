@@ -0,0 +1,31 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::ssa::SsaMode;
+use cs6120::ssa_verify;
+
+fn main() {
+    // usage: to_ssa [minimal|semi-pruned|pruned]
+    let mode = match std::env::args().nth(1).as_deref() {
+        None | Some("minimal") => SsaMode::Minimal,
+        Some("semi-pruned") => SsaMode::SemiPruned,
+        Some("pruned") => SsaMode::Pruned,
+        Some(mode) => panic!("unknown mode: {}", mode),
+    };
+    let mut p = load_program();
+    for f in p.functions.iter_mut() {
+        if f.instrs.is_empty() {
+            continue;
+        }
+        let mut cfg = cfg::Cfg::build(&basic_block::basic_blocks(&f.instrs));
+        let phis = cfg.to_ssa(&f.args, mode);
+        eprintln!("{}: {} phi(s)", f.name, phis);
+        let errors = ssa_verify::verify(&cfg, &f.args);
+        for e in errors.iter() {
+            eprintln!("{}: {}", f.name, e);
+        }
+        assert!(errors.is_empty(), "to_ssa produced invalid SSA");
+        f.instrs = cfg.to_instrs();
+    }
+    output_program(&p);
+}
@@ -0,0 +1,21 @@
+use bril_rs::*;
+use cs6120::basic_block;
+use cs6120::cfg;
+use cs6120::ssa_verify;
+
+fn main() {
+    let p = load_program();
+    for f in p.functions {
+        if f.instrs.is_empty() {
+            continue;
+        }
+        let cfg = cfg::Cfg::build(&basic_block::basic_blocks(&f.instrs));
+        let errors = ssa_verify::verify(&cfg, &f.args);
+        if errors.is_empty() {
+            println!("{}: ok", f.name);
+        }
+        for e in errors {
+            println!("{}: {}", f.name, e);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Formatting of bril programs in the textual form (as `bril2txt` does).
+use bril_rs::*;
+
+pub fn fmt_type(ty: &Type) -> String {
+    match ty {
+        Type::Int => "int".to_owned(),
+        Type::Bool => "bool".to_owned(),
+        Type::Float => "float".to_owned(),
+        Type::Pointer(ty) => format!("ptr<{}>", fmt_type(ty)),
+    }
+}
+
+pub fn fmt_literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(i) => i.to_string(),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Float(f) => format!("{:?}", f),
+    }
+}
+
+/// `op @funcs args .labels`
+fn fmt_operation(op: &str, args: &[String], funcs: &[String], labels: &[String]) -> String {
+    let mut s = op.to_owned();
+    for f in funcs {
+        s += &format!(" @{}", f);
+    }
+    for a in args {
+        s += &format!(" {}", a);
+    }
+    for l in labels {
+        s += &format!(" .{}", l);
+    }
+    s
+}
+
+/// The opcode as it is written in bril, from its serialized form.
+fn op_name(op: serde_json::Result<serde_json::Value>) -> String {
+    match op {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => unreachable!(),
+    }
+}
+
+pub fn fmt_instruction(ins: &Instruction) -> String {
+    match ins {
+        Instruction::Constant {
+            dest,
+            const_type,
+            value,
+            ..
+        } => format!(
+            "{}: {} = const {};",
+            dest,
+            fmt_type(const_type),
+            fmt_literal(value)
+        ),
+        Instruction::Value {
+            op,
+            dest,
+            op_type,
+            args,
+            funcs,
+            labels,
+            ..
+        } => format!(
+            "{}: {} = {};",
+            dest,
+            fmt_type(op_type),
+            fmt_operation(&op_name(serde_json::to_value(op)), args, funcs, labels)
+        ),
+        Instruction::Effect {
+            op,
+            args,
+            funcs,
+            labels,
+            ..
+        } => format!(
+            "{};",
+            fmt_operation(&op_name(serde_json::to_value(op)), args, funcs, labels)
+        ),
+    }
+}
+
+pub fn fmt_code(code: &Code) -> String {
+    match code {
+        Code::Label { label } => format!(".{}:", label),
+        Code::Instruction(ins) => fmt_instruction(ins),
+    }
+}
@@ -11,45 +11,241 @@ fn extract_label(block: &[Code]) -> (Option<String>, &[Code]) {
     }
 }
 
+/// The position of an instruction: (block name, index in `CfgNode::block`)
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct InstructionId(String, usize);
+impl InstructionId {
+    pub fn new(block: String, index: usize) -> Self {
+        Self(block, index)
+    }
+    pub fn block(&self) -> &str {
+        &self.0
+    }
+    pub fn index(&self) -> usize {
+        self.1
+    }
+}
+impl std::fmt::Display for InstructionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.0, self.1)
+    }
+}
+impl std::fmt::Debug for InstructionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+/// How control reaches a successor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// falling off the end of the block
+    Fallthrough,
+    /// `jmp` (or any other instruction with labels)
+    Jump,
+    /// the first label of `br`
+    BranchTrue,
+    /// the second label of `br`
+    BranchFalse,
+}
+impl std::fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Jump => "jump",
+            EdgeKind::BranchTrue => "true",
+            EdgeKind::BranchFalse => "false",
+        };
+        f.write_str(s)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CfgNode {
     pub name: String,
     pub block: Vec<Code>,
     pub prev: HashSet<String>,
-    pub next: HashSet<String>,
+    /// successors without duplicates: label targets in instruction order,
+    /// then the fall-through unless it is a label target as well
+    pub next: Vec<String>,
+    /// the successor reached by falling off the end of the block, which may also be a label target
+    pub fallthrough: Option<String>,
 }
+impl CfgNode {
+    /// Check whether the block ends with `jmp`, `br` or `ret`.
+    pub fn is_terminated(&self) -> bool {
+        matches!(self.block.last(), Some(Code::Instruction(ins)) if is_terminator(ins))
+    }
+
+    /// The successor reached by falling off the end of the block, if any.
+    pub fn fallthrough(&self) -> Option<&str> {
+        self.fallthrough.as_deref()
+    }
+
+    /// Outgoing edges in the order of `next`.
+    ///
+    /// Unlike `next`, a `br` whose labels are the same yields both of its edges,
+    /// and so does a block with a label edge to the block it falls through to.
+    pub fn edges(&self) -> Vec<(&str, EdgeKind)> {
+        let mut edges = Vec::new();
+        for code in self.block.iter() {
+            if let Code::Instruction(Instruction::Effect { op, labels, .. }) = code {
+                match (op, labels.as_slice()) {
+                    (EffectOps::Branch, [t, f]) => {
+                        edges.push((t.as_str(), EdgeKind::BranchTrue));
+                        edges.push((f.as_str(), EdgeKind::BranchFalse));
+                    }
+                    _ => edges.extend(labels.iter().map(|l| (l.as_str(), EdgeKind::Jump))),
+                }
+            }
+        }
+        if let Some(nx) = self.fallthrough() {
+            edges.push((nx, EdgeKind::Fallthrough));
+        }
+        edges
+    }
+
+    /// The successor taken when the block's `br` condition is `cond`.
+    pub fn branch_target(&self, cond: bool) -> Option<&str> {
+        let kind = if cond {
+            EdgeKind::BranchTrue
+        } else {
+            EdgeKind::BranchFalse
+        };
+        self.edges()
+            .into_iter()
+            .find(|(_, k)| *k == kind)
+            .map(|(nx, _)| nx)
+    }
+
+    pub fn has_phi(&self) -> bool {
+        self.block.iter().any(|code| {
+            matches!(
+                code,
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Phi,
+                    ..
+                })
+            )
+        })
+    }
+
+    /// Make the phis in the block refer to the predecessor `new` instead of `old`.
+    pub fn rename_phi_label(&mut self, old: &str, new: &str) {
+        for code in self.block.iter_mut() {
+            if let Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                labels,
+                ..
+            }) = code
+            {
+                for l in labels.iter_mut().filter(|l| *l == old) {
+                    *l = new.to_owned();
+                }
+            }
+        }
+    }
+
+    /// Drop the phi arguments whose labels are not predecessors of the block.
+    pub fn prune_phi_labels(&mut self) {
+        let prev = &self.prev;
+        for code in self.block.iter_mut() {
+            if let Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                args,
+                labels,
+                ..
+            }) = code
+            {
+                let (kept_args, kept_labels) = args
+                    .iter()
+                    .zip(labels.iter())
+                    .filter(|(_, l)| prev.contains(*l))
+                    .map(|(a, l)| (a.clone(), l.clone()))
+                    .unzip();
+                *args = kept_args;
+                *labels = kept_labels;
+            }
+        }
+    }
+
+    /// Redirect the edge to `old` so that it goes to `new`.
+    pub fn retarget(&mut self, old: &str, new: &str) {
+        for code in self.block.iter_mut() {
+            if let Code::Instruction(Instruction::Effect { labels, .. }) = code {
+                for l in labels.iter_mut().filter(|l| *l == old) {
+                    *l = new.to_owned();
+                }
+            }
+        }
+        if self.fallthrough.as_deref() == Some(old) {
+            self.fallthrough = Some(new.to_owned());
+        }
+        if let Some(i) = self.next.iter().position(|nx| nx == old) {
+            if self.next.iter().any(|nx| nx == new) {
+                self.next.remove(i);
+            } else {
+                self.next[i] = new.to_owned();
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cfg {
     pub entry: String,
     pub nodes: HashMap<String, CfgNode>,
+    /// block names in the order of the original program (`entry` comes first)
+    pub order: Vec<String>,
 }
 impl Cfg {
     #[allow(dead_code)]
     pub fn build(blocks: &[Vec<Code>]) -> Cfg {
         let mut nodes: HashMap<String, CfgNode> = HashMap::new();
+        let mut order = Vec::new();
         let mut entry: Option<String> = None;
 
+        // generated names must not collide with any label in the function
+        let labels: HashSet<&String> = blocks
+            .iter()
+            .flatten()
+            .filter_map(|code| match code {
+                Code::Label { label } => Some(label),
+                _ => None,
+            })
+            .collect();
+
         let mut pred_name = None;
         for b in blocks.iter().filter(|b| !b.is_empty()) {
             let (name, b) = extract_label(b);
-            let name = name.unwrap_or_else(|| format!("bb{}", nodes.len()));
+            let name = name.unwrap_or_else(|| {
+                let base = format!("bb{}", nodes.len());
+                std::iter::once(base.clone())
+                    .chain((1..).map(|i| format!("{}.{}", base, i)))
+                    .find(|name| !labels.contains(name))
+                    .unwrap()
+            });
 
-            let next = b
-                .iter()
-                .filter_map(|code| match code {
-                    Code::Instruction(Instruction::Effect { labels, .. }) => Some(labels),
-                    _ => None,
-                })
-                .flatten()
-                .cloned()
-                .collect();
+            let mut next: Vec<String> = Vec::new();
+            for code in b {
+                if let Code::Instruction(Instruction::Effect { labels, .. }) = code {
+                    for l in labels {
+                        if !next.contains(l) {
+                            next.push(l.clone());
+                        }
+                    }
+                }
+            }
 
             // update predecessor's "next"
             if let Some(pred) = pred_name
                 .take()
                 .and_then(|pred_name| nodes.get_mut(&pred_name))
             {
-                pred.next.insert(name.clone());
+                if !pred.next.contains(&name) {
+                    pred.next.push(name.clone());
+                }
+                pred.fallthrough = Some(name.clone());
             }
 
             nodes.insert(
@@ -59,8 +255,10 @@ impl Cfg {
                     block: b.to_vec(),
                     next,
                     prev: HashSet::new(),
+                    fallthrough: None,
                 },
             );
+            order.push(name.clone());
             if entry.is_none() {
                 entry = Some(name.clone());
             }
@@ -74,12 +272,147 @@ impl Cfg {
         let mut cfg = Cfg {
             entry: entry.expect("empty cfg"),
             nodes,
+            order,
         };
         cfg.refresh_prev();
         cfg
     }
 
-    fn refresh_prev(&mut self) {
+    /// Linearize the blocks into a function body.
+    ///
+    /// A block falling through is followed by its successor if possible, otherwise
+    /// an explicit `jmp` (or `ret` when it falls off the end) is added.
+    /// Blocks are labeled with their names if they have predecessors or a phi refers to them.
+    /// An empty entry block is labeled as well, so that it is kept apart from the block
+    /// it falls through to.
+    pub fn to_instrs(&self) -> Vec<Code> {
+        let phi_labels: HashSet<&String> = self
+            .nodes
+            .values()
+            .flat_map(|node| node.block.iter())
+            .filter_map(|code| match code {
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Phi,
+                    labels,
+                    ..
+                }) => Some(labels),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        let mut layout: Vec<&str> = Vec::with_capacity(self.nodes.len());
+        let mut placed = HashSet::new();
+        for name in self.order.iter() {
+            let mut cur = name.as_str();
+            // follow the chain of fall-through edges
+            while placed.insert(cur) {
+                layout.push(cur);
+                match self.nodes[cur].fallthrough() {
+                    Some(nx) => cur = nx,
+                    None => break,
+                }
+            }
+        }
+
+        let mut instrs = Vec::new();
+        for (i, name) in layout.iter().enumerate() {
+            let node = &self.nodes[*name];
+            let empty_entry = *name == self.entry && node.block.is_empty() && layout.len() > 1;
+            if !node.prev.is_empty() || phi_labels.contains(&node.name) || empty_entry {
+                instrs.push(Code::Label {
+                    label: name.to_string(),
+                });
+            }
+            instrs.extend(node.block.iter().cloned());
+
+            if node.is_terminated() {
+                continue;
+            }
+            let following = layout.get(i + 1).copied();
+            match node.fallthrough() {
+                Some(nx) if Some(nx) != following => {
+                    instrs.push(Code::Instruction(Instruction::Effect {
+                        op: EffectOps::Jump,
+                        args: Vec::new(),
+                        funcs: Vec::new(),
+                        labels: vec![nx.to_owned()],
+                    }));
+                }
+                None if following.is_some() => {
+                    instrs.push(Code::Instruction(Instruction::Effect {
+                        op: EffectOps::Return,
+                        args: Vec::new(),
+                        funcs: Vec::new(),
+                        labels: Vec::new(),
+                    }));
+                }
+                _ => {}
+            }
+        }
+        instrs
+    }
+
+    /// The code which `id` refers to.
+    pub fn code(&self, id: &InstructionId) -> Option<&Code> {
+        self.nodes.get(id.block())?.block.get(id.index())
+    }
+
+    /// Block names in reverse postorder of a depth-first search from `entry`.
+    /// Unreachable blocks follow in the original order.
+    pub fn reverse_postorder(&self) -> Vec<String> {
+        let mut postorder = Vec::with_capacity(self.nodes.len());
+        let mut visited = HashSet::new();
+        visited.insert(self.entry.as_str());
+        let mut stack = vec![(self.entry.as_str(), self.next_reversed(&self.entry))];
+        while let Some((name, next)) = stack.last_mut() {
+            match next.pop() {
+                Some(nx) => {
+                    if visited.insert(nx) {
+                        let nx_next = self.next_reversed(nx);
+                        stack.push((nx, nx_next));
+                    }
+                }
+                None => {
+                    postorder.push(name.to_string());
+                    stack.pop();
+                }
+            }
+        }
+        postorder.reverse();
+
+        let unreachable = self
+            .order
+            .iter()
+            .filter(|name| !visited.contains(name.as_str()))
+            .cloned();
+        postorder.extend(unreachable);
+        postorder
+    }
+
+    /// successors in reverse, so that popping visits them in the order of `next`
+    fn next_reversed(&self, name: &str) -> Vec<&str> {
+        self.nodes[name]
+            .next
+            .iter()
+            .rev()
+            .map(|s| s.as_str())
+            .collect()
+    }
+
+    /// A block name starting with `base` which is not in use.
+    pub fn fresh_name(&self, base: &str) -> String {
+        std::iter::once(base.to_owned())
+            .chain((1..).map(|i| format!("{}.{}", base, i)))
+            .find(|name| !self.nodes.contains_key(name))
+            .unwrap()
+    }
+
+    /// Recompute `prev` of every node from `next`.
+    pub fn refresh_prev(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.prev.clear();
+        }
         let mut prev_map: HashMap<String, HashSet<String>> = HashMap::new();
         for (name, node) in self.nodes.iter() {
             for nx in node.next.iter() {
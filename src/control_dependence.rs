@@ -0,0 +1,61 @@
+use crate::cfg::Cfg;
+use crate::dominance::Dominators;
+use std::collections::{HashMap, HashSet};
+
+/// Control dependence between blocks:
+/// `b` is control dependent on `a` if `a` decides whether `b` is executed,
+/// i.e. `b` post-dominates some successor of `a` but does not strictly post-dominate `a`.
+///
+/// Blocks executed whenever the function is entered do not depend on any block.
+#[derive(Debug, Clone, Default)]
+pub struct ControlDependence {
+    /// block --> blocks it is control dependent on
+    deps: HashMap<String, HashSet<String>>,
+    /// block --> blocks control dependent on it
+    dependents: HashMap<String, HashSet<String>>,
+}
+
+impl ControlDependence {
+    pub fn build(cfg: &Cfg) -> Self {
+        let pdom = Dominators::compute_post(cfg);
+        let mut cd = Self::default();
+        for (a, node) in cfg.nodes.iter() {
+            // a block with one successor decides nothing. this also ignores the edge to
+            // the synthetic exit which `compute_post` adds to an infinite loop.
+            if node.next.len() < 2 || !pdom.is_reachable(a) {
+                continue;
+            }
+            let ipdom = pdom.idom(a);
+            for b in node.next.iter() {
+                // walk up the post-dominator tree from `b` to the immediate post-dominator of `a`.
+                // NOTE: `None` is the synthetic exit.
+                let mut runner = Some(b.as_str()).filter(|r| pdom.is_reachable(r));
+                while let Some(r) = runner {
+                    if Some(r) == ipdom {
+                        break;
+                    }
+                    cd.add(a, r);
+                    runner = pdom.idom(r);
+                }
+            }
+        }
+        cd
+    }
+
+    fn add(&mut self, controller: &str, dependent: &str) {
+        let deps = self.deps.entry(dependent.to_owned()).or_default();
+        deps.insert(controller.to_owned());
+        let dependents = self.dependents.entry(controller.to_owned()).or_default();
+        dependents.insert(dependent.to_owned());
+    }
+
+    /// blocks which `b` is control dependent on
+    pub fn depends_on(&self, b: &str) -> impl Iterator<Item = &String> {
+        self.deps.get(b).into_iter().flatten()
+    }
+
+    /// blocks which are control dependent on `a`
+    pub fn dependents(&self, a: &str) -> impl Iterator<Item = &String> {
+        self.dependents.get(a).into_iter().flatten()
+    }
+}
@@ -1,114 +1,592 @@
+pub use crate::cfg::InstructionId;
 use crate::cfg::{Cfg, CfgNode};
+use crate::lattice::{Lattice, MapLattice, MaySet, MustSet};
+use crate::lvn::is_commutative;
 use bril_rs::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 
-pub type AnalysisResult<Set> = HashMap<String, (Set, Set)>;
+pub struct AnalysisResult<Set> {
+    /// block name --> (entry, exit)
+    sets: HashMap<String, (Set, Set)>,
+    /// block names in reverse postorder
+    order: Vec<String>,
+    /// the number of times `transfer` was applied until convergence
+    pub iterations: usize,
+}
+impl<Set> AnalysisResult<Set> {
+    pub fn get(&self, name: &str) -> Option<&(Set, Set)> {
+        self.sets.get(name)
+    }
+
+    /// (block name, (entry, exit)) in reverse postorder
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &(Set, Set))> {
+        self.order.iter().map(move |name| (name, &self.sets[name]))
+    }
+}
 
+/// Facts immediately before and after each instruction, in program order.
+pub type InstructionResult<Set> = HashMap<InstructionId, (Set, Set)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// Every block starts with `Set::top()`, and the sets flowing into a block are combined by `meet`.
 pub trait DataFlowAnalysisBase {
-    type Set;
+    type Set: Lattice;
+    const DIRECTION: Direction;
+
+    /// The effect of the single instruction `ins`, which is `node.block[idx]`.
+    fn transfer_instruction(
+        node: &CfgNode,
+        idx: usize,
+        ins: &Instruction,
+        set: Self::Set,
+    ) -> Self::Set;
+
+    fn transfer(node: &CfgNode, set: Self::Set) -> Self::Set {
+        let instrs = instructions(node);
+        match Self::DIRECTION {
+            Direction::Forward => instrs.fold(set, |set, (i, ins)| {
+                Self::transfer_instruction(node, i, ins, set)
+            }),
+            Direction::Backward => instrs.rev().fold(set, |set, (i, ins)| {
+                Self::transfer_instruction(node, i, ins, set)
+            }),
+        }
+    }
 
-    fn transfer(node: &CfgNode, entry: Self::Set) -> Self::Set;
     fn edge(exit: &CfgNode, entry: &CfgNode, exit: Self::Set) -> Self::Set;
-    fn merge(sets: Vec<Self::Set>) -> Self::Set;
 }
 
 pub trait DataFlowAnalysis: DataFlowAnalysisBase {
-    fn drive(cfg: &Cfg, init: Self::Set) -> AnalysisResult<Self::Set>;
+    fn drive(cfg: &Cfg, init: Self::Set) -> AnalysisResult<Self::Set> {
+        match Self::DIRECTION {
+            Direction::Forward => drive_forward::<Self>(cfg, init),
+            Direction::Backward => drive_backward::<Self>(cfg, init),
+        }
+    }
+
+    /// Replay `transfer_instruction` over a solved `result`
+    /// to obtain the facts immediately before and after each instruction.
+    fn instruction_results(
+        cfg: &Cfg,
+        result: &AnalysisResult<Self::Set>,
+    ) -> InstructionResult<Self::Set> {
+        let mut facts = InstructionResult::new();
+        for (name, (entry, exit)) in result.iter() {
+            let node = &cfg.nodes[name];
+            match Self::DIRECTION {
+                Direction::Forward => {
+                    let mut set = entry.clone();
+                    for (i, ins) in instructions(node) {
+                        let after = Self::transfer_instruction(node, i, ins, set.clone());
+                        facts.insert(InstructionId::new(name.clone(), i), (set, after.clone()));
+                        set = after;
+                    }
+                }
+                Direction::Backward => {
+                    let mut set = exit.clone();
+                    for (i, ins) in instructions(node).rev() {
+                        let before = Self::transfer_instruction(node, i, ins, set.clone());
+                        facts.insert(InstructionId::new(name.clone(), i), (before.clone(), set));
+                        set = before;
+                    }
+                }
+            }
+        }
+        facts
+    }
 }
 
-fn drive_forward<A>(cfg: &Cfg, init: A::Set) -> AnalysisResult<A::Set>
-where
-    A: DataFlowAnalysisBase,
-    A::Set: Clone + Default + PartialEq,
-{
-    let mut result = AnalysisResult::new();
-    for name in cfg.nodes.keys() {
-        result.insert(name.clone(), (A::Set::default(), A::Set::default()));
+/// (index in the block, instruction) of every instruction in `node`
+fn instructions(node: &CfgNode) -> impl DoubleEndedIterator<Item = (usize, &Instruction)> {
+    node.block
+        .iter()
+        .enumerate()
+        .filter_map(|(i, code)| match code {
+            Code::Label { .. } => None,
+            Code::Instruction(ins) => Some((i, ins)),
+        })
+}
+
+/// A worklist which always yields the pending block that comes first in `order`.
+struct Worklist<'a> {
+    order: Vec<&'a str>,
+    index: HashMap<&'a str, usize>,
+    pending: BTreeSet<usize>,
+}
+impl<'a> Worklist<'a> {
+    /// Every block is pending at first, since each has to be visited at least once.
+    fn new(order: Vec<&'a str>) -> Self {
+        let index = order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let pending = (0..order.len()).collect();
+        Self {
+            order,
+            index,
+            pending,
+        }
+    }
+    fn push(&mut self, name: &str) {
+        self.pending.insert(self.index[name]);
     }
+    fn pop(&mut self) -> Option<&'a str> {
+        let i = *self.pending.iter().next()?;
+        self.pending.remove(&i);
+        Some(self.order[i])
+    }
+}
 
-    let mut worklist = HashSet::new();
-    worklist.insert(cfg.entry.clone());
+fn drive_forward<A: DataFlowAnalysisBase + ?Sized>(
+    cfg: &Cfg,
+    init: A::Set,
+) -> AnalysisResult<A::Set> {
+    let order = cfg.reverse_postorder();
+    let mut sets = HashMap::new();
+    for name in cfg.nodes.keys() {
+        sets.insert(name.clone(), (A::Set::top(), A::Set::top()));
+    }
+    let mut iterations = 0;
 
-    while !worklist.is_empty() {
-        let name = worklist.iter().next().unwrap().clone();
-        worklist.remove(&name);
+    let mut worklist = Worklist::new(order.iter().map(|s| s.as_str()).collect());
+    while let Some(name) = worklist.pop() {
+        iterations += 1;
 
-        let node = cfg.nodes.get(&name).unwrap();
+        let node = &cfg.nodes[name];
         let mut out_p: Vec<_> = node
             .prev
             .iter()
             .map(|p| {
-                let s = result.get(p).unwrap().1.clone();
-                A::edge(cfg.nodes.get(p).unwrap(), node, s)
+                let s = sets[p].1.clone();
+                A::edge(&cfg.nodes[p], node, s)
             })
             .collect();
         if name == cfg.entry {
             out_p.push(init.clone());
         }
 
-        let reach = A::merge(out_p);
-        let (entry, exit) = result.get_mut(&name).unwrap();
+        let reach = out_p.iter().fold(A::Set::top(), |acc, s| acc.meet(s));
+        let (entry, exit) = sets.get_mut(name).unwrap();
         *entry = reach.clone();
-        let reach = A::transfer(&node, reach);
+        let reach = A::transfer(node, reach);
+        debug_assert!(reach.less_equal(exit), "transfer must be monotone");
         if exit != &reach {
             *exit = reach;
             for nx in &node.next {
-                worklist.insert(nx.clone());
+                worklist.push(nx);
             }
         }
     }
-    result
+    AnalysisResult {
+        sets,
+        order,
+        iterations,
+    }
 }
 
-#[allow(dead_code, unused_variables)]
-fn drive_backward<A: DataFlowAnalysisBase>(cfg: &Cfg, init: A::Set) -> AnalysisResult<A::Set> {
-    todo!()
+fn drive_backward<A: DataFlowAnalysisBase + ?Sized>(
+    cfg: &Cfg,
+    init: A::Set,
+) -> AnalysisResult<A::Set> {
+    let order = cfg.reverse_postorder();
+    let mut sets = HashMap::new();
+    for name in cfg.nodes.keys() {
+        sets.insert(name.clone(), (A::Set::top(), A::Set::top()));
+    }
+    let mut iterations = 0;
+
+    // postorder
+    let mut worklist = Worklist::new(order.iter().rev().map(|s| s.as_str()).collect());
+    while let Some(name) = worklist.pop() {
+        iterations += 1;
+
+        let node = &cfg.nodes[name];
+        let mut in_s: Vec<_> = node
+            .next
+            .iter()
+            .map(|s| {
+                let set = sets[s].0.clone();
+                A::edge(node, &cfg.nodes[s], set)
+            })
+            .collect();
+        // every block without successors leaves the function (`ret` or falling off the end)
+        if node.next.is_empty() {
+            in_s.push(init.clone());
+        }
+
+        let live = in_s.iter().fold(A::Set::top(), |acc, s| acc.meet(s));
+        let (entry, exit) = sets.get_mut(name).unwrap();
+        *exit = live.clone();
+        let live = A::transfer(node, live);
+        debug_assert!(live.less_equal(entry), "transfer must be monotone");
+        if entry != &live {
+            *entry = live;
+            for pr in &node.prev {
+                worklist.push(pr);
+            }
+        }
+    }
+    AnalysisResult {
+        sets,
+        order,
+        iterations,
+    }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct InstructionId(String, usize);
+/// A definition which may reach a use of a variable.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub enum Definition {
+    /// the variable is a function argument
+    Argument,
+    /// the variable has not been assigned yet
+    Undefined,
+    Instruction(InstructionId),
+}
+impl std::fmt::Debug for Definition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Argument => write!(f, "arg"),
+            Self::Undefined => write!(f, "undef"),
+            Self::Instruction(id) => write!(f, "{}", id),
+        }
+    }
+}
 
 pub struct ReachingDefinition(());
 
-impl DataFlowAnalysisBase for ReachingDefinition {
-    type Set = HashMap<String, HashSet<InstructionId>>;
-
-    fn transfer(node: &CfgNode, mut reaching_set: Self::Set) -> Self::Set {
-        for (i, code) in node.block.iter().enumerate() {
-            let ins = match code {
-                Code::Label { .. } => continue,
-                Code::Instruction(ins) => ins,
-            };
-            match ins {
-                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
-                    let mut instrs = HashSet::new();
-                    instrs.insert(InstructionId(node.name.clone(), i));
-                    reaching_set.insert(dest.clone(), instrs);
+impl ReachingDefinition {
+    /// The set at the function entry:
+    /// `args` are defined as `Argument` and every other variable in `cfg` is `Undefined`.
+    pub fn init(cfg: &Cfg, args: &[Argument]) -> <Self as DataFlowAnalysisBase>::Set {
+        let mut init: <Self as DataFlowAnalysisBase>::Set = cfg
+            .nodes
+            .values()
+            .flat_map(instructions)
+            .flat_map(|(_, ins)| match ins {
+                Instruction::Constant { dest, .. } => vec![dest],
+                Instruction::Value { dest, args, .. } => {
+                    std::iter::once(dest).chain(args.iter()).collect()
+                }
+                Instruction::Effect { args, .. } => args.iter().collect(),
+            })
+            .map(|var| {
+                let undef = std::iter::once(Definition::Undefined).collect();
+                (var.clone(), undef)
+            })
+            .collect();
+        for arg in args {
+            let def = std::iter::once(Definition::Argument).collect();
+            init.insert(arg.name.clone(), def);
+        }
+        init
+    }
+
+    /// Uses of variables which may be reached by `Undefined` on some path,
+    /// as (instruction, variable) in reverse postorder of blocks.
+    /// The arguments of `phi` are not checked since they are used on the incoming edges.
+    pub fn undefined_uses(
+        cfg: &Cfg,
+        result: &AnalysisResult<<Self as DataFlowAnalysisBase>::Set>,
+    ) -> Vec<(InstructionId, String)> {
+        let facts = Self::instruction_results(cfg, result);
+        let mut uses = Vec::new();
+        for (name, _) in result.iter() {
+            for (i, ins) in instructions(&cfg.nodes[name]) {
+                let args = match ins {
+                    Instruction::Value {
+                        op: ValueOps::Phi, ..
+                    } => continue,
+                    Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+                    Instruction::Constant { .. } => continue,
+                };
+                let id = InstructionId::new(name.clone(), i);
+                let (before, _) = &facts[&id];
+                for arg in args {
+                    if before.get(arg).contains(&Definition::Undefined) {
+                        uses.push((id.clone(), arg.clone()));
+                    }
                 }
-                _ => {}
             }
         }
+        uses
+    }
+}
+
+impl DataFlowAnalysisBase for ReachingDefinition {
+    type Set = MapLattice<String, MaySet<Definition>>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn transfer_instruction(
+        node: &CfgNode,
+        idx: usize,
+        ins: &Instruction,
+        mut reaching_set: Self::Set,
+    ) -> Self::Set {
+        if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = ins {
+            let id = InstructionId::new(node.name.clone(), idx);
+            let defs = std::iter::once(Definition::Instruction(id)).collect();
+            reaching_set.insert(dest.clone(), defs);
+        }
         reaching_set
     }
 
     fn edge(_exit: &CfgNode, _entry: &CfgNode, exit: Self::Set) -> Self::Set {
         exit
     }
+}
+
+impl DataFlowAnalysis for ReachingDefinition {}
 
-    fn merge(sets: Vec<Self::Set>) -> Self::Set {
-        let mut u = Self::Set::new();
-        for s in sets {
-            for (name, instrs) in s {
-                let tmp = u.entry(name).or_insert_with(HashSet::new);
-                tmp.extend(instrs);
+pub struct LiveVariables(());
+
+impl DataFlowAnalysisBase for LiveVariables {
+    type Set = MaySet<String>;
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn transfer_instruction(
+        _node: &CfgNode,
+        _idx: usize,
+        ins: &Instruction,
+        mut live_set: Self::Set,
+    ) -> Self::Set {
+        if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = ins {
+            live_set.remove(dest);
+        }
+        if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } = ins {
+            live_set.extend(args.iter().cloned());
+        }
+        live_set
+    }
+
+    fn edge(_exit: &CfgNode, _entry: &CfgNode, entry: Self::Set) -> Self::Set {
+        entry
+    }
+}
+
+impl DataFlowAnalysis for LiveVariables {}
+
+/// The value of a variable in constant propagation.
+#[derive(Debug, Clone)]
+pub enum ConstValue {
+    /// no definition has been seen yet
+    Top,
+    Const(Literal),
+    /// not a constant
+    Bottom,
+}
+impl Lattice for ConstValue {
+    fn top() -> Self {
+        Self::Top
+    }
+    fn bottom() -> Self {
+        Self::Bottom
+    }
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Top, x) | (x, Self::Top) => x.clone(),
+            (Self::Const(_), Self::Const(_)) if self == other => self.clone(),
+            _ => Self::Bottom,
+        }
+    }
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Bottom, x) | (x, Self::Bottom) => x.clone(),
+            (Self::Const(_), Self::Const(_)) if self == other => self.clone(),
+            _ => Self::Top,
+        }
+    }
+}
+impl ConstValue {
+    /// The value of `op` applied to arguments with the values `args`.
+    /// A phi is the meet of its arguments.
+    pub fn eval(op: ValueOps, args: &[ConstValue]) -> ConstValue {
+        match op {
+            ValueOps::Phi => args.iter().fold(ConstValue::Top, |acc, v| acc.meet(v)),
+            _ if args.iter().any(|v| v == &ConstValue::Bottom) => ConstValue::Bottom,
+            _ if args.iter().any(|v| v == &ConstValue::Top) => ConstValue::Top,
+            _ => {
+                let lits: Vec<_> = args
+                    .iter()
+                    .map(|v| match v {
+                        ConstValue::Const(lit) => lit.clone(),
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                match eval_value_op(op, &lits) {
+                    Some(lit) => ConstValue::Const(lit),
+                    None => ConstValue::Bottom,
+                }
+            }
+        }
+    }
+}
+impl PartialEq for ConstValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Top, Self::Top) | (Self::Bottom, Self::Bottom) => true,
+            // compare floats bitwise so that NaN does not prevent convergence
+            (Self::Const(Literal::Float(a)), Self::Const(Literal::Float(b))) => {
+                a.to_bits() == b.to_bits()
             }
+            (Self::Const(a), Self::Const(b)) => a == b,
+            _ => false,
         }
-        u
     }
 }
 
-impl DataFlowAnalysis for ReachingDefinition {
-    fn drive(cfg: &Cfg, init: Self::Set) -> AnalysisResult<Self::Set> {
-        drive_forward::<Self>(cfg, init)
+/// Evaluate `op` on constant arguments.
+/// Returns `None` if the result is not a compile-time constant (e.g. `call`, division by zero).
+pub fn eval_value_op(op: ValueOps, args: &[Literal]) -> Option<Literal> {
+    use Literal::*;
+    use ValueOps::*;
+    let lit = match (op, args) {
+        (Id, [x]) => x.clone(),
+
+        (Add, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (Sub, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (Mul, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (Div, [Int(_), Int(0)]) => return None,
+        (Div, [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        (Eq, [Int(a), Int(b)]) => Bool(a == b),
+        (Lt, [Int(a), Int(b)]) => Bool(a < b),
+        (Gt, [Int(a), Int(b)]) => Bool(a > b),
+        (Le, [Int(a), Int(b)]) => Bool(a <= b),
+        (Ge, [Int(a), Int(b)]) => Bool(a >= b),
+
+        (Not, [Bool(a)]) => Bool(!a),
+        (And, [Bool(a), Bool(b)]) => Bool(*a && *b),
+        (Or, [Bool(a), Bool(b)]) => Bool(*a || *b),
+
+        (Fadd, [Float(a), Float(b)]) => Float(a + b),
+        (Fsub, [Float(a), Float(b)]) => Float(a - b),
+        (Fmul, [Float(a), Float(b)]) => Float(a * b),
+        (Fdiv, [Float(a), Float(b)]) => Float(a / b),
+        (Feq, [Float(a), Float(b)]) => Bool(a == b),
+        (Flt, [Float(a), Float(b)]) => Bool(a < b),
+        (Fgt, [Float(a), Float(b)]) => Bool(a > b),
+        (Fle, [Float(a), Float(b)]) => Bool(a <= b),
+        (Fge, [Float(a), Float(b)]) => Bool(a >= b),
+
+        _ => return None,
+    };
+    Some(lit)
+}
+
+/// Normalize a literal so that it matches its declared type
+/// (e.g. `x: float = const 1` is parsed as an integer literal).
+pub fn typed_literal(ty: &Type, value: &Literal) -> Literal {
+    match (ty, value) {
+        (Type::Float, Literal::Int(i)) => Literal::Float(*i as f64),
+        _ => value.clone(),
     }
 }
+
+pub struct ConstantPropagation(());
+
+impl DataFlowAnalysisBase for ConstantPropagation {
+    type Set = MapLattice<String, ConstValue>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn transfer_instruction(
+        _node: &CfgNode,
+        _idx: usize,
+        ins: &Instruction,
+        mut const_set: Self::Set,
+    ) -> Self::Set {
+        match ins {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value = typed_literal(const_type, value);
+                const_set.insert(dest.clone(), ConstValue::Const(value));
+            }
+            Instruction::Value { dest, op, args, .. } => {
+                let vals: Vec<_> = args.iter().map(|arg| const_set.get(arg).clone()).collect();
+                const_set.insert(dest.clone(), ConstValue::eval(*op, &vals));
+            }
+            Instruction::Effect { .. } => {}
+        }
+        const_set
+    }
+
+    fn edge(_exit: &CfgNode, _entry: &CfgNode, exit: Self::Set) -> Self::Set {
+        exit
+    }
+}
+
+impl DataFlowAnalysis for ConstantPropagation {}
+
+/// A pure computation, identified by its operator and argument variables.
+/// Like `lvn::Expr`, the arguments of commutative operators are sorted.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Expr {
+    pub op: ValueOps,
+    pub args: Vec<String>,
+}
+impl Expr {
+    /// Returns `None` if `ins` is not a pure value operation.
+    pub fn from_instruction(ins: &Instruction) -> Option<Expr> {
+        match ins {
+            Instruction::Value { op, args, .. } => match op {
+                ValueOps::Call
+                | ValueOps::Id
+                | ValueOps::Alloc
+                | ValueOps::Load
+                | ValueOps::Phi => None,
+                _ => {
+                    let mut expr = Expr {
+                        op: *op,
+                        args: args.clone(),
+                    };
+                    expr.normalize();
+                    Some(expr)
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn normalize(&mut self) {
+        if is_commutative(self.op) {
+            self.args.sort_unstable();
+        }
+    }
+}
+
+pub struct AvailableExpressions(());
+
+impl DataFlowAnalysisBase for AvailableExpressions {
+    type Set = MustSet<Expr>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn transfer_instruction(
+        _node: &CfgNode,
+        _idx: usize,
+        ins: &Instruction,
+        mut avail_set: Self::Set,
+    ) -> Self::Set {
+        let dest = match ins {
+            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => dest,
+            Instruction::Effect { .. } => return avail_set,
+        };
+        // redefining `dest` kills every expression which uses it
+        avail_set.retain(|expr| !expr.args.contains(dest));
+        if let Some(expr) = Expr::from_instruction(ins) {
+            if !expr.args.contains(dest) {
+                avail_set.insert(expr);
+            }
+        }
+        avail_set
+    }
+
+    fn edge(_exit: &CfgNode, _entry: &CfgNode, exit: Self::Set) -> Self::Set {
+        exit
+    }
+}
+
+impl DataFlowAnalysis for AvailableExpressions {}
@@ -0,0 +1,95 @@
+use crate::cfg::{Cfg, InstructionId};
+use crate::data_flow_framework::{
+    AnalysisResult, DataFlowAnalysis, DataFlowAnalysisBase, Definition, ReachingDefinition,
+};
+use bril_rs::*;
+use std::collections::{HashMap, HashSet};
+
+type ReachingSet = <ReachingDefinition as DataFlowAnalysisBase>::Set;
+
+/// A use of the variable `var` by the instruction `id`
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Use {
+    pub id: InstructionId,
+    pub var: String,
+}
+
+/// Def-use and use-def chains
+#[derive(Debug, Clone, Default)]
+pub struct DefUse {
+    /// definition --> uses reached by it
+    uses: HashMap<InstructionId, HashSet<Use>>,
+    /// use --> definitions reaching it
+    defs: HashMap<Use, HashSet<Definition>>,
+}
+
+impl DefUse {
+    /// Build the chains from the result of `ReachingDefinition`.
+    pub fn build(cfg: &Cfg, result: &AnalysisResult<ReachingSet>) -> Self {
+        let facts = ReachingDefinition::instruction_results(cfg, result);
+        let mut du = DefUse::default();
+        for (name, _) in result.iter() {
+            for (i, code) in cfg.nodes[name].block.iter().enumerate() {
+                let ins = match code {
+                    Code::Label { .. } => continue,
+                    Code::Instruction(ins) => ins,
+                };
+                let id = InstructionId::new(name.clone(), i);
+                match ins {
+                    // each argument of `phi` is used at the end of the corresponding predecessor
+                    Instruction::Value {
+                        op: ValueOps::Phi,
+                        args,
+                        labels,
+                        ..
+                    } => {
+                        for (arg, label) in args.iter().zip(labels.iter()) {
+                            let reaching = match result.get(label) {
+                                Some((_, exit)) => exit,
+                                None => &facts[&id].0,
+                            };
+                            du.add(&id, arg, reaching);
+                        }
+                    }
+                    Instruction::Value { args, .. } | Instruction::Effect { args, .. } => {
+                        for arg in args {
+                            du.add(&id, arg, &facts[&id].0);
+                        }
+                    }
+                    Instruction::Constant { .. } => {}
+                }
+            }
+        }
+        du
+    }
+
+    fn add(&mut self, id: &InstructionId, var: &str, reaching: &ReachingSet) {
+        let u = Use {
+            id: id.clone(),
+            var: var.to_owned(),
+        };
+        let defs = self.defs.entry(u.clone()).or_default();
+        for def in reaching.get(&u.var).elements().into_iter().flatten() {
+            defs.insert(def.clone());
+            if let Definition::Instruction(def) = def {
+                let uses = self.uses.entry(def.clone()).or_default();
+                uses.insert(u.clone());
+            }
+        }
+    }
+
+    /// uses reached by the definition `def`
+    pub fn uses(&self, def: &InstructionId) -> impl Iterator<Item = &Use> {
+        self.uses.get(def).into_iter().flatten()
+    }
+
+    /// definitions reaching the use `u`
+    pub fn defs(&self, u: &Use) -> impl Iterator<Item = &Definition> {
+        self.defs.get(u).into_iter().flatten()
+    }
+
+    /// every use in the function
+    pub fn all_uses(&self) -> impl Iterator<Item = &Use> {
+        self.defs.keys()
+    }
+}
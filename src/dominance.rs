@@ -0,0 +1,297 @@
+use crate::cfg::Cfg;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// block --> its immediate dominator, or `None` for the entry
+    idom: HashMap<String, Option<String>>,
+    /// block --> its (preorder, postorder) numbers in the dominator tree
+    numbers: HashMap<String, (usize, usize)>,
+}
+
+impl Dominators {
+    /// Compute the dominators of every block reachable from `cfg.entry`.
+    pub fn compute(cfg: &Cfg) -> Self {
+        Self::build(
+            &[cfg.entry.as_str()],
+            |b| cfg.nodes[b].next.iter().map(|s| s.as_str()).collect(),
+            |b| cfg.nodes[b].prev.iter().map(|s| s.as_str()).collect(),
+        )
+    }
+
+    /// Compute the post-dominators of every block,
+    /// i.e. the dominators on the reverse `cfg` whose entry is a synthetic exit
+    /// joining all `ret` blocks and the blocks falling off the end.
+    /// `idom(b)` is then the immediate post-dominator, or `None` if it is the synthetic exit.
+    ///
+    /// NOTE: an infinite loop never reaches the exit, so one of its blocks
+    /// (the first to finish in a depth-first search from the entry) is joined to the exit as well.
+    pub fn compute_post(cfg: &Cfg) -> Self {
+        let exits: Vec<&String> = cfg
+            .order
+            .iter()
+            .filter(|name| cfg.nodes[*name].next.is_empty())
+            .collect();
+        let mut postorder = cfg.reverse_postorder();
+        postorder.reverse();
+
+        let mut roots: Vec<&str> = Vec::new();
+        let mut reaches_exit: HashSet<&str> = HashSet::new();
+        for b in exits.into_iter().chain(postorder.iter()) {
+            if reaches_exit.contains(b.as_str()) {
+                continue;
+            }
+            roots.push(b);
+            let mut stack = vec![b.as_str()];
+            while let Some(b) = stack.pop() {
+                if reaches_exit.insert(b) {
+                    stack.extend(cfg.nodes[b].prev.iter().map(|s| s.as_str()));
+                }
+            }
+        }
+        Self::build(
+            &roots,
+            |b| cfg.nodes[b].prev.iter().map(|s| s.as_str()).collect(),
+            |b| cfg.nodes[b].next.iter().map(|s| s.as_str()).collect(),
+        )
+    }
+
+    /// The algorithm of Cooper, Harvey and Kennedy ("A Simple, Fast Dominance Algorithm")
+    /// on the graph given by `succs` and `preds`, entered through a virtual root in front of `roots`.
+    ///
+    /// Blocks are numbered in reverse postorder with the virtual root as 0,
+    /// so that walking up the tree always goes to a smaller number.
+    fn build<'a>(
+        roots: &[&'a str],
+        succs: impl Fn(&'a str) -> Vec<&'a str>,
+        preds: impl Fn(&'a str) -> Vec<&'a str>,
+    ) -> Self {
+        let mut postorder = Vec::new();
+        let mut visited: HashSet<&str> = HashSet::new();
+        for &r in roots {
+            if !visited.insert(r) {
+                continue;
+            }
+            let reversed = |b| {
+                let mut next = succs(b);
+                next.reverse();
+                next
+            };
+            let mut stack = vec![(r, reversed(r))];
+            while let Some((name, next)) = stack.last_mut() {
+                match next.pop() {
+                    Some(nx) => {
+                        if visited.insert(nx) {
+                            stack.push((nx, reversed(nx)));
+                        }
+                    }
+                    None => {
+                        postorder.push(*name);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        let rpo: Vec<&str> = postorder.into_iter().rev().collect();
+        let index: HashMap<&str, usize> =
+            rpo.iter().enumerate().map(|(i, b)| (*b, i + 1)).collect();
+        let n = rpo.len() + 1;
+
+        let mut pred_indices: Vec<Vec<usize>> = vec![Vec::new()];
+        for &b in rpo.iter() {
+            let mut ps: Vec<usize> = preds(b)
+                .into_iter()
+                .filter_map(|p| index.get(p).copied())
+                .collect();
+            if roots.contains(&b) {
+                ps.push(0);
+            }
+            pred_indices.push(ps);
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; n];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 1..n {
+                let mut new_idom = None;
+                for &p in pred_indices[b].iter().filter(|p| idom[**p].is_some()) {
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(q) => intersect(&idom, p, q),
+                    });
+                }
+                if new_idom != idom[b] {
+                    idom[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        // number the tree so that `a` dominates `b` iff `b` is inside the subtree of `a`
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for b in 1..n {
+            children[idom[b].unwrap()].push(b);
+        }
+        let mut numbers = vec![(0, 0); n];
+        let (mut pre, mut post) = (0, 0);
+        let mut stack = vec![(0, false)];
+        while let Some((b, expanded)) = stack.pop() {
+            if expanded {
+                numbers[b].1 = post;
+                post += 1;
+            } else {
+                numbers[b].0 = pre;
+                pre += 1;
+                stack.push((b, true));
+                stack.extend(children[b].iter().map(|c| (*c, false)));
+            }
+        }
+
+        let name = |i: usize| rpo[i - 1].to_owned();
+        Self {
+            idom: (1..n)
+                .map(|b| match idom[b].unwrap() {
+                    0 => (name(b), None),
+                    p => (name(b), Some(name(p))),
+                })
+                .collect(),
+            numbers: (1..n).map(|b| (name(b), numbers[b])).collect(),
+        }
+    }
+
+    /// Blocks dominating `b` (including `b` itself), from `b` up to the entry.
+    /// Unreachable blocks have no dominators.
+    pub fn dominators(&self, b: &str) -> impl Iterator<Item = &String> + '_ {
+        let start = self.idom.get_key_value(b).map(|(b, _)| b);
+        std::iter::successors(start, move |d| self.idom[d.as_str()].as_ref())
+    }
+
+    /// The immediate dominator of `b`, or `None` for the entry and unreachable blocks.
+    pub fn idom(&self, b: &str) -> Option<&str> {
+        self.idom.get(b).and_then(|d| d.as_deref())
+    }
+
+    /// Check whether `a` dominates `b`.
+    pub fn dominates(&self, a: &str, b: &str) -> bool {
+        match (self.numbers.get(a), self.numbers.get(b)) {
+            (Some((pre_a, post_a)), Some((pre_b, post_b))) => pre_a <= pre_b && post_b <= post_a,
+            _ => false,
+        }
+    }
+
+    pub fn is_reachable(&self, b: &str) -> bool {
+        self.idom.contains_key(b)
+    }
+}
+
+/// The closest common dominator of `a` and `b`, by reverse postorder numbers.
+fn intersect(idom: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while a > b {
+            a = idom[a].unwrap();
+        }
+        while b > a {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+/// The tree in which the parent of each block is its immediate dominator
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    pub root: String,
+    /// block --> blocks immediately dominated by it, in name order
+    children: HashMap<String, Vec<String>>,
+    dom: Dominators,
+}
+
+impl DominatorTree {
+    pub fn new(cfg: &Cfg) -> Self {
+        let dom = Dominators::compute(cfg);
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for (b, parent) in dom.idom.iter() {
+            if let Some(parent) = parent {
+                children.entry(parent.clone()).or_default().push(b.clone());
+            }
+        }
+        for ch in children.values_mut() {
+            ch.sort();
+        }
+        Self {
+            root: cfg.entry.clone(),
+            children,
+            dom,
+        }
+    }
+
+    pub fn children(&self, b: &str) -> &[String] {
+        self.children.get(b).map(|ch| ch.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn parent(&self, b: &str) -> Option<&str> {
+        self.dom.idom(b)
+    }
+
+    /// Check whether `a` dominates `b`.
+    pub fn dominates(&self, a: &str, b: &str) -> bool {
+        self.dom.dominates(a, b)
+    }
+
+    pub fn dominators(&self) -> &Dominators {
+        &self.dom
+    }
+
+    pub fn preorder(&self) -> Vec<String> {
+        let mut order = Vec::new();
+        let mut stack = vec![self.root.clone()];
+        while let Some(b) = stack.pop() {
+            stack.extend(self.children(&b).iter().rev().cloned());
+            order.push(b);
+        }
+        order
+    }
+
+    pub fn postorder(&self) -> Vec<String> {
+        let mut order = Vec::new();
+        let mut stack = vec![(self.root.clone(), false)];
+        while let Some((b, expanded)) = stack.pop() {
+            if expanded {
+                order.push(b);
+            } else {
+                stack.push((b.clone(), true));
+                stack.extend(self.children(&b).iter().rev().map(|c| (c.clone(), false)));
+            }
+        }
+        order
+    }
+
+    /// The dominance frontier of every reachable block:
+    /// DF(a) = { b | a dominates a predecessor of b, but does not strictly dominate b }
+    pub fn frontiers(&self, cfg: &Cfg) -> HashMap<String, HashSet<String>> {
+        let mut df: HashMap<String, HashSet<String>> = HashMap::new();
+        for name in self.preorder() {
+            df.entry(name).or_default();
+        }
+        for (b, node) in cfg.nodes.iter() {
+            if !self.dom.is_reachable(b) {
+                continue;
+            }
+            let idom = self.parent(b);
+            for p in node.prev.iter().filter(|p| self.dom.is_reachable(p)) {
+                // walk up from the predecessor until reaching the immediate dominator of `b`
+                let mut runner = Some(p.as_str());
+                while let Some(r) = runner {
+                    if Some(r) == idom {
+                        break;
+                    }
+                    df.get_mut(r).unwrap().insert(b.clone());
+                    runner = self.parent(r);
+                }
+            }
+        }
+        df
+    }
+}
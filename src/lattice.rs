@@ -0,0 +1,406 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+
+/// A bounded lattice of data-flow facts.
+///
+/// Facts start at `top` (no information yet) and only move down by `meet`,
+/// which is how the facts of several control-flow paths are combined.
+pub trait Lattice: Clone + PartialEq {
+    /// the greatest element: the identity of `meet`
+    fn top() -> Self;
+    /// the least element: the identity of `join`
+    fn bottom() -> Self;
+    /// greatest lower bound
+    fn meet(&self, other: &Self) -> Self;
+    /// least upper bound
+    fn join(&self, other: &Self) -> Self;
+    /// partial order: `self ⊑ other`
+    fn less_equal(&self, other: &Self) -> bool {
+        &self.meet(other) == self
+    }
+}
+
+/// A subset of `T` which may also be the set of every `T`.
+#[derive(Clone, PartialEq)]
+pub enum PowerSet<T: Hash + Eq> {
+    Finite(HashSet<T>),
+    Universe,
+}
+impl<T: Hash + Eq + Clone> PowerSet<T> {
+    pub fn empty() -> Self {
+        Self::Finite(HashSet::new())
+    }
+
+    pub fn contains(&self, x: &T) -> bool {
+        match self {
+            Self::Finite(s) => s.contains(x),
+            Self::Universe => true,
+        }
+    }
+
+    /// The elements, or `None` for the universe.
+    pub fn elements(&self) -> Option<&HashSet<T>> {
+        match self {
+            Self::Finite(s) => Some(s),
+            Self::Universe => None,
+        }
+    }
+
+    pub fn insert(&mut self, x: T) {
+        if let Self::Finite(s) = self {
+            s.insert(x);
+        }
+    }
+
+    /// NOTE: the universe is left unchanged since its complement cannot be represented.
+    pub fn remove(&mut self, x: &T) {
+        if let Self::Finite(s) = self {
+            s.remove(x);
+        }
+    }
+
+    /// NOTE: the universe is left unchanged since its complement cannot be represented.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        if let Self::Finite(s) = self {
+            s.retain(f);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Finite(a), Self::Finite(b)) => Self::Finite(a.union(b).cloned().collect()),
+            _ => Self::Universe,
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Finite(a), Self::Finite(b)) => {
+                Self::Finite(a.intersection(b).cloned().collect())
+            }
+            (Self::Finite(s), Self::Universe) | (Self::Universe, Self::Finite(s)) => {
+                Self::Finite(s.clone())
+            }
+            (Self::Universe, Self::Universe) => Self::Universe,
+        }
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Finite(a), Self::Finite(b)) => a.is_subset(b),
+            (_, Self::Universe) => true,
+            (Self::Universe, Self::Finite(_)) => false,
+        }
+    }
+}
+impl<T: Hash + Eq> Extend<T> for PowerSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        if let Self::Finite(s) = self {
+            s.extend(iter);
+        }
+    }
+}
+impl<T: Hash + Eq + fmt::Debug> fmt::Debug for PowerSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Finite(s) => f.debug_set().entries(sorted_debug(s.iter())).finish(),
+            Self::Universe => write!(f, "Universe"),
+        }
+    }
+}
+
+/// Sets for may-analyses: `meet` is union, so `top` is the empty set.
+#[derive(Clone, PartialEq)]
+pub struct MaySet<T: Hash + Eq>(pub PowerSet<T>);
+
+/// Sets for must-analyses: `meet` is intersection, so `top` is the universe.
+#[derive(Clone, PartialEq)]
+pub struct MustSet<T: Hash + Eq>(pub PowerSet<T>);
+
+impl<T: Hash + Eq + Clone> Lattice for MaySet<T> {
+    fn top() -> Self {
+        Self(PowerSet::empty())
+    }
+    fn bottom() -> Self {
+        Self(PowerSet::Universe)
+    }
+    fn meet(&self, other: &Self) -> Self {
+        Self(self.0.union(&other.0))
+    }
+    fn join(&self, other: &Self) -> Self {
+        Self(self.0.intersection(&other.0))
+    }
+    fn less_equal(&self, other: &Self) -> bool {
+        other.0.is_subset(&self.0)
+    }
+}
+
+impl<T: Hash + Eq + Clone> Lattice for MustSet<T> {
+    fn top() -> Self {
+        Self(PowerSet::Universe)
+    }
+    fn bottom() -> Self {
+        Self(PowerSet::empty())
+    }
+    fn meet(&self, other: &Self) -> Self {
+        Self(self.0.intersection(&other.0))
+    }
+    fn join(&self, other: &Self) -> Self {
+        Self(self.0.union(&other.0))
+    }
+    fn less_equal(&self, other: &Self) -> bool {
+        self.0.is_subset(&other.0)
+    }
+}
+
+macro_rules! impl_set_wrapper {
+    ($name:ident) => {
+        impl<T: Hash + Eq> Deref for $name<T> {
+            type Target = PowerSet<T>;
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+        impl<T: Hash + Eq> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+        impl<T: Hash + Eq> FromIterator<T> for $name<T> {
+            fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+                Self(PowerSet::Finite(iter.into_iter().collect()))
+            }
+        }
+        impl<T: Hash + Eq + fmt::Debug> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+impl_set_wrapper!(MaySet);
+impl_set_wrapper!(MustSet);
+
+/// Pointwise lattice of maps: keys which are not stored explicitly map to `rest`.
+#[derive(Clone)]
+pub struct MapLattice<K: Hash + Eq, V> {
+    map: HashMap<K, V>,
+    rest: V,
+}
+impl<K: Hash + Eq + Clone, V: Lattice> MapLattice<K, V> {
+    pub fn get(&self, key: &K) -> &V {
+        self.map.get(key).unwrap_or(&self.rest)
+    }
+
+    pub fn insert(&mut self, key: K, val: V) {
+        self.map.insert(key, val);
+    }
+
+    /// The entries stored explicitly.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map.iter()
+    }
+
+    fn pointwise<F: Fn(&V, &V) -> V>(&self, other: &Self, f: F) -> Self {
+        let map = self
+            .map
+            .keys()
+            .chain(other.map.keys())
+            .map(|k| (k.clone(), f(self.get(k), other.get(k))))
+            .collect();
+        Self {
+            map,
+            rest: f(&self.rest, &other.rest),
+        }
+    }
+}
+impl<K: Hash + Eq + Clone, V: Lattice> Lattice for MapLattice<K, V> {
+    fn top() -> Self {
+        Self {
+            map: HashMap::new(),
+            rest: V::top(),
+        }
+    }
+    fn bottom() -> Self {
+        Self {
+            map: HashMap::new(),
+            rest: V::bottom(),
+        }
+    }
+    fn meet(&self, other: &Self) -> Self {
+        self.pointwise(other, V::meet)
+    }
+    fn join(&self, other: &Self) -> Self {
+        self.pointwise(other, V::join)
+    }
+    fn less_equal(&self, other: &Self) -> bool {
+        self.rest.less_equal(&other.rest)
+            && self
+                .map
+                .keys()
+                .chain(other.map.keys())
+                .all(|k| self.get(k).less_equal(other.get(k)))
+    }
+}
+impl<K: Hash + Eq + Clone, V: Lattice> PartialEq for MapLattice<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rest == other.rest
+            && self
+                .map
+                .keys()
+                .chain(other.map.keys())
+                .all(|k| self.get(k) == other.get(k))
+    }
+}
+impl<K: Hash + Eq + Clone, V: Lattice> FromIterator<(K, V)> for MapLattice<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().collect(),
+            rest: V::top(),
+        }
+    }
+}
+impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug> fmt::Debug for MapLattice<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<_> = self
+            .map
+            .iter()
+            .map(|(k, v)| (Verbatim(format!("{:?}", k)), v))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+/// Prints its content as it is, to debug-print pre-formatted elements.
+struct Verbatim(String);
+impl fmt::Debug for Verbatim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Debug representations of `items` in sorted order, so that output does not depend on hashing.
+fn sorted_debug<'a, T: fmt::Debug + 'a>(items: impl Iterator<Item = &'a T>) -> Vec<Verbatim> {
+    let mut items: Vec<_> = items.map(|x| Verbatim(format!("{:?}", x))).collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn may(xs: &[i32]) -> MaySet<i32> {
+        xs.iter().copied().collect()
+    }
+
+    fn must(xs: &[i32]) -> MustSet<i32> {
+        xs.iter().copied().collect()
+    }
+
+    fn map(entries: Vec<(&'static str, MaySet<i32>)>) -> MapLattice<&'static str, MaySet<i32>> {
+        entries.into_iter().collect()
+    }
+
+    /// Check the lattice laws on every combination of `samples`.
+    fn check_laws<L: Lattice + fmt::Debug>(samples: &[L]) {
+        let (top, bottom) = (L::top(), L::bottom());
+        for a in samples {
+            assert_eq!(a.meet(a), *a);
+            assert_eq!(a.join(a), *a);
+            assert_eq!(a.meet(&top), *a);
+            assert_eq!(a.join(&bottom), *a);
+            assert_eq!(a.meet(&bottom), bottom);
+            assert_eq!(a.join(&top), top);
+            assert!(bottom.less_equal(a) && a.less_equal(&top));
+            for b in samples {
+                let (m, j) = (a.meet(b), a.join(b));
+                assert_eq!(m, b.meet(a));
+                assert_eq!(j, b.join(a));
+                // absorption
+                assert_eq!(a.meet(&j), *a);
+                assert_eq!(a.join(&m), *a);
+                assert!(m.less_equal(a) && m.less_equal(b));
+                assert!(a.less_equal(&j) && b.less_equal(&j));
+                assert_eq!(a.less_equal(b), m == *a, "{:?} <= {:?}", a, b);
+                for c in samples {
+                    assert_eq!(a.meet(&b.meet(c)), m.meet(c));
+                    assert_eq!(a.join(&b.join(c)), j.join(c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn may_set_laws() {
+        check_laws(&[
+            MaySet::top(),
+            MaySet::bottom(),
+            may(&[1]),
+            may(&[2]),
+            may(&[1, 2]),
+            may(&[1, 2, 3]),
+        ]);
+    }
+
+    #[test]
+    fn must_set_laws() {
+        check_laws(&[
+            MustSet::top(),
+            MustSet::bottom(),
+            must(&[1]),
+            must(&[2]),
+            must(&[1, 2]),
+            must(&[1, 2, 3]),
+        ]);
+    }
+
+    #[test]
+    fn map_lattice_laws() {
+        let mut below_rest = MapLattice::bottom();
+        below_rest.insert("x", may(&[1]));
+        check_laws(&[
+            MapLattice::top(),
+            MapLattice::bottom(),
+            map(vec![("x", may(&[1]))]),
+            map(vec![("x", may(&[1])), ("y", may(&[2]))]),
+            map(vec![("y", MaySet::bottom())]),
+            below_rest,
+        ]);
+    }
+
+    #[test]
+    fn power_set_universe() {
+        let one = may(&[1]).0;
+        let universe = PowerSet::Universe;
+        assert_eq!(one.intersection(&universe), one);
+        assert_eq!(universe.intersection(&one), one);
+        assert_eq!(universe.intersection(&universe), universe);
+        assert_eq!(one.union(&universe), universe);
+        assert!(one.is_subset(&universe));
+        assert!(universe.is_subset(&universe));
+        assert!(!universe.is_subset(&one));
+    }
+
+    #[test]
+    fn map_lattice_rest() {
+        // an entry equal to `rest` is the same as no entry
+        assert_eq!(map(vec![("x", MaySet::top())]), MapLattice::top());
+        assert_ne!(map(vec![("x", may(&[1]))]), MapLattice::top());
+
+        // keys stored on one side only are compared against `rest` of the other
+        let x = map(vec![("x", may(&[1]))]);
+        let top = MapLattice::top();
+        let bottom = MapLattice::bottom();
+        assert!(x.less_equal(&top));
+        assert!(!top.less_equal(&x));
+        assert!(bottom.less_equal(&x));
+        assert!(!x.less_equal(&bottom));
+        assert_eq!(*bottom.get(&"y"), MaySet::bottom());
+        assert_eq!(*x.meet(&bottom).get(&"x"), MaySet::bottom());
+    }
+}
@@ -1,5 +1,17 @@
 pub mod basic_block;
+pub mod bril_text;
 pub mod cfg;
+pub mod control_dependence;
 pub mod data_flow_framework;
 pub mod dead_code_elim;
+pub mod def_use;
+pub mod dominance;
+pub mod lattice;
+pub mod loops;
 pub mod lvn;
+pub mod normalize;
+pub mod reducibility;
+pub mod sccp;
+pub mod simplify;
+pub mod ssa;
+pub mod ssa_verify;
@@ -0,0 +1,134 @@
+use crate::cfg::Cfg;
+use crate::dominance::Dominators;
+use std::collections::HashSet;
+
+/// A natural loop. Back edges sharing a header are merged into one loop.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: String,
+    /// blocks in the loop, including the header
+    pub body: HashSet<String>,
+    /// sources of the back edges, in name order
+    pub latches: Vec<String>,
+    /// edges leaving the loop as (inside, outside), in name order
+    pub exits: Vec<(String, String)>,
+    /// index of the innermost enclosing loop
+    pub parent: Option<usize>,
+    /// indices of the loops immediately nested in this loop
+    pub children: Vec<usize>,
+    /// 1 for outermost loops
+    pub depth: usize,
+}
+
+/// The loop-nesting forest.
+/// Enclosing loops come before the loops nested in them.
+#[derive(Debug, Clone)]
+pub struct LoopForest {
+    pub loops: Vec<Loop>,
+    /// indices of the outermost loops
+    pub roots: Vec<usize>,
+}
+
+impl LoopForest {
+    pub fn build(cfg: &Cfg) -> Self {
+        let dom = Dominators::compute(cfg);
+
+        // back edges: n -> h where h dominates n
+        let mut headers: Vec<(String, Vec<String>)> = Vec::new();
+        for name in cfg.reverse_postorder() {
+            let mut latches: Vec<_> = cfg.nodes[&name]
+                .prev
+                .iter()
+                .filter(|p| dom.dominates(&name, p))
+                .cloned()
+                .collect();
+            if !latches.is_empty() {
+                latches.sort();
+                headers.push((name, latches));
+            }
+        }
+
+        let mut loops: Vec<Loop> = headers
+            .into_iter()
+            .map(|(header, latches)| {
+                let body = natural_loop(cfg, &dom, &header, &latches);
+                let mut exits: Vec<_> = body
+                    .iter()
+                    .flat_map(|b| {
+                        cfg.nodes[b]
+                            .next
+                            .iter()
+                            .filter(|nx| !body.contains(*nx))
+                            .map(move |nx| (b.clone(), nx.clone()))
+                    })
+                    .collect();
+                exits.sort();
+                Loop {
+                    header,
+                    body,
+                    latches,
+                    exits,
+                    parent: None,
+                    children: Vec::new(),
+                    depth: 1,
+                }
+            })
+            .collect();
+        // natural loops with distinct headers are either disjoint or nested
+        loops.sort_by(|a, b| {
+            b.body
+                .len()
+                .cmp(&a.body.len())
+                .then_with(|| a.header.cmp(&b.header))
+        });
+
+        let mut roots = Vec::new();
+        for i in 0..loops.len() {
+            let parent = (0..i)
+                .rev()
+                .find(|&j| loops[j].body.contains(&loops[i].header));
+            match parent {
+                Some(j) => {
+                    loops[i].parent = Some(j);
+                    loops[i].depth = loops[j].depth + 1;
+                    loops[j].children.push(i);
+                }
+                None => roots.push(i),
+            }
+        }
+        Self { loops, roots }
+    }
+
+    /// The innermost loop containing `b`
+    pub fn innermost(&self, b: &str) -> Option<usize> {
+        // inner loops come later
+        (0..self.loops.len())
+            .rev()
+            .find(|&i| self.loops[i].body.contains(b))
+    }
+
+    /// The number of loops containing `b`
+    pub fn depth(&self, b: &str) -> usize {
+        self.innermost(b).map_or(0, |i| self.loops[i].depth)
+    }
+}
+
+/// The header and every block which reaches a latch without passing through the header.
+/// Predecessors not dominated by the header (e.g. unreachable ones) are not part of the loop.
+fn natural_loop(cfg: &Cfg, dom: &Dominators, header: &str, latches: &[String]) -> HashSet<String> {
+    let mut body = HashSet::new();
+    body.insert(header.to_owned());
+    let mut stack: Vec<String> = latches.to_vec();
+    while let Some(b) = stack.pop() {
+        if body.insert(b.clone()) {
+            stack.extend(
+                cfg.nodes[&b]
+                    .prev
+                    .iter()
+                    .filter(|p| dom.dominates(header, p))
+                    .cloned(),
+            );
+        }
+    }
+    body
+}
@@ -9,14 +9,8 @@ enum Expr {
 }
 impl Expr {
     fn normalize(&mut self) {
-        use ValueOps::*;
         match self {
-            Self::Value { op: Add, args }
-            | Self::Value { op: Fadd, args }
-            | Self::Value { op: Mul, args }
-            | Self::Value { op: Fmul, args }
-            | Self::Value { op: Eq, args }
-            | Self::Value { op: Feq, args } => {
+            Self::Value { op, args } if is_commutative(*op) => {
                 // for commutative operator, sort its arguments.
                 args.sort_unstable();
             }
@@ -25,6 +19,12 @@ impl Expr {
     }
 }
 
+/// Check whether the order of `op`'s arguments is irrelevant.
+pub(crate) fn is_commutative(op: ValueOps) -> bool {
+    use ValueOps::*;
+    matches!(op, Add | Fadd | Mul | Fmul | Eq | Feq)
+}
+
 #[derive(Clone, Default)]
 struct Table {
     /// variable name --> value number
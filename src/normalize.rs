@@ -0,0 +1,177 @@
+use crate::cfg::{Cfg, CfgNode};
+use bril_rs::*;
+use std::collections::HashSet;
+
+impl Cfg {
+    /// Split every edge from a block with several successors to a block with several
+    /// predecessors by inserting an empty block on it.
+    ///
+    /// Returns the names of the inserted blocks.
+    pub fn split_critical_edges(&mut self) -> Vec<String> {
+        let mut critical = Vec::new();
+        for name in self.order.iter() {
+            let node = &self.nodes[name];
+            if node.next.len() < 2 {
+                continue;
+            }
+            for nx in node.next.iter() {
+                if self.nodes[nx].prev.len() > 1 {
+                    critical.push((name.clone(), nx.clone()));
+                }
+            }
+        }
+
+        critical
+            .into_iter()
+            .map(|(from, to)| self.split_edge(&from, &to))
+            .collect()
+    }
+
+    /// Insert an empty block on the edge `from` --> `to` and return its name.
+    /// Phis in `to` are updated to refer to the new block.
+    pub fn split_edge(&mut self, from: &str, to: &str) -> String {
+        let name = self.fresh_name(&format!("{}.{}", from, to));
+        self.add_empty_block(&name, to);
+        self.nodes.get_mut(from).unwrap().retarget(to, &name);
+        let to = self.nodes.get_mut(to).unwrap();
+        to.rename_phi_label(from, &name);
+        to.prev.remove(from);
+        to.prev.insert(name.clone());
+        self.nodes
+            .get_mut(&name)
+            .unwrap()
+            .prev
+            .insert(from.to_owned());
+        name
+    }
+
+    /// Make sure that `entry` has no predecessors by adding an empty block in front of it.
+    ///
+    /// Returns the name of the new entry, if one was added.
+    pub fn add_entry_block(&mut self) -> Option<String> {
+        if self.nodes[&self.entry].prev.is_empty() {
+            return None;
+        }
+        let name = self.fresh_name("entry");
+        let old = std::mem::replace(&mut self.entry, name.clone());
+        self.add_empty_block(&name, &old);
+        // the entry has to be laid out first
+        let placed = self.order.pop().unwrap();
+        self.order.insert(0, placed);
+        self.refresh_prev();
+        Some(name)
+    }
+
+    /// Make every path leaving the function go through one block, which is returned.
+    ///
+    /// If several blocks leave the function, they jump to a new exit block instead.
+    /// A returned value is copied into a fresh variable of type `ret_type` first,
+    /// which the exit block then returns.
+    pub fn add_exit_block(&mut self, ret_type: Option<&Type>) -> String {
+        let exits: Vec<String> = self
+            .order
+            .iter()
+            .filter(|name| self.nodes[*name].next.is_empty())
+            .cloned()
+            .collect();
+        if exits.len() == 1 {
+            return exits[0].clone();
+        }
+
+        let name = self.fresh_name("exit");
+        let ret_var = ret_type.map(|ty| (self.fresh_var("__ret"), ty.clone()));
+        for e in exits.iter() {
+            let node = self.nodes.get_mut(e).unwrap();
+            if let Some(Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args,
+                ..
+            })) = node.block.last()
+            {
+                let copy = match (&ret_var, args.first()) {
+                    (Some((var, ty)), Some(arg)) => Some(Code::Instruction(Instruction::Value {
+                        op: ValueOps::Id,
+                        dest: var.clone(),
+                        op_type: ty.clone(),
+                        args: vec![arg.clone()],
+                        funcs: Vec::new(),
+                        labels: Vec::new(),
+                    })),
+                    _ => None,
+                };
+                node.block.pop();
+                node.block.extend(copy);
+            }
+            node.block.push(Code::Instruction(Instruction::Effect {
+                op: EffectOps::Jump,
+                args: Vec::new(),
+                funcs: Vec::new(),
+                labels: vec![name.clone()],
+            }));
+            node.next.push(name.clone());
+        }
+
+        let ret = Code::Instruction(Instruction::Effect {
+            op: EffectOps::Return,
+            args: ret_var.into_iter().map(|(var, _)| var).collect(),
+            funcs: Vec::new(),
+            labels: Vec::new(),
+        });
+        self.nodes.insert(
+            name.clone(),
+            CfgNode {
+                name: name.clone(),
+                block: vec![ret],
+                prev: HashSet::new(),
+                next: Vec::new(),
+                fallthrough: None,
+            },
+        );
+        self.order.push(name.clone());
+        self.refresh_prev();
+        name
+    }
+
+    /// Add an empty block `name` falling through to `to` at the end of `order`.
+    fn add_empty_block(&mut self, name: &str, to: &str) {
+        self.nodes.insert(
+            name.to_owned(),
+            CfgNode {
+                name: name.to_owned(),
+                block: Vec::new(),
+                prev: HashSet::new(),
+                next: vec![to.to_owned()],
+                fallthrough: Some(to.to_owned()),
+            },
+        );
+        self.order.push(name.to_owned());
+    }
+
+    /// A variable name starting with `base` which is not used in the function.
+    pub(crate) fn fresh_var(&self, base: &str) -> String {
+        self.fresh_vars(base, 1).pop().unwrap()
+    }
+
+    /// `n` distinct variable names starting with `base` which are not used in the function.
+    pub(crate) fn fresh_vars(&self, base: &str, n: usize) -> Vec<String> {
+        let mut used: HashSet<&String> = HashSet::new();
+        for code in self.nodes.values().flat_map(|node| node.block.iter()) {
+            match code {
+                Code::Instruction(Instruction::Constant { dest, .. }) => {
+                    used.insert(dest);
+                }
+                Code::Instruction(Instruction::Value { dest, args, .. }) => {
+                    used.insert(dest);
+                    used.extend(args);
+                }
+                Code::Instruction(Instruction::Effect { args, .. }) => used.extend(args),
+                Code::Label { .. } => {}
+            }
+        }
+        std::iter::once(base.to_owned())
+            .chain((1..).map(|i| format!("{}.{}", base, i)))
+            .filter(|name| !used.contains(name))
+            .take(n)
+            .collect()
+    }
+}
@@ -0,0 +1,201 @@
+use crate::cfg::Cfg;
+use std::collections::{HashMap, HashSet};
+
+impl Cfg {
+    /// Strongly connected regions which can be entered at more than one block.
+    /// The graph is reducible if and only if there are none.
+    ///
+    /// Each region is sorted by name. Unreachable blocks are ignored.
+    pub fn irreducible_regions(&self) -> Vec<Vec<String>> {
+        let reachable = self.reachable();
+        let mut regions = Vec::new();
+        self.find_irreducible(&reachable, &reachable, &mut regions);
+        regions
+    }
+
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible_regions().is_empty()
+    }
+
+    /// Make the graph reducible by node splitting:
+    /// for each irreducible region, every entry but one gets its own copy of the region.
+    pub fn make_reducible(&mut self) {
+        while let Some(region) = self.irreducible_regions().into_iter().next() {
+            let reachable = self.reachable();
+            let region: HashSet<String> = region.into_iter().collect();
+            let mut entries = self.region_entries(&region, &reachable);
+            let header = entries.remove(0);
+            let rest: HashSet<String> = region.iter().filter(|b| **b != header).cloned().collect();
+
+            for e in entries {
+                let mut preds: Vec<String> = self.nodes[&e]
+                    .prev
+                    .iter()
+                    .filter(|p| !region.contains(*p) && reachable.contains(*p))
+                    .cloned()
+                    .collect();
+                preds.sort();
+                let copies = self.copy_blocks(&self.reachable_within(&e, &rest));
+                for p in preds {
+                    self.nodes.get_mut(&p).unwrap().retarget(&e, &copies[&e]);
+                }
+            }
+            self.refresh_prev();
+        }
+    }
+
+    /// Copy `blocks` with fresh names, so that the edges between them go to the copies.
+    /// Returns original name --> name of the copy.
+    fn copy_blocks(&mut self, blocks: &[String]) -> HashMap<String, String> {
+        let mut copies = HashMap::new();
+        for b in blocks {
+            let name = self.fresh_name(b);
+            let mut node = self.nodes[b].clone();
+            node.name = name.clone();
+            self.nodes.insert(name.clone(), node);
+            self.order.push(name.clone());
+            copies.insert(b.clone(), name);
+        }
+        for copy in copies.values() {
+            let node = self.nodes.get_mut(copy).unwrap();
+            let targets = node.next.clone();
+            for t in targets {
+                if let Some(new) = copies.get(&t) {
+                    node.retarget(&t, new);
+                }
+            }
+        }
+        copies
+    }
+
+    /// Blocks reachable from `entry`.
+    pub(crate) fn reachable(&self) -> HashSet<String> {
+        let all: HashSet<String> = self.nodes.keys().cloned().collect();
+        self.reachable_within(&self.entry, &all)
+            .into_iter()
+            .collect()
+    }
+
+    /// Blocks in `nodes` reachable from `start` without leaving `nodes`, in visiting order.
+    fn reachable_within(&self, start: &str, nodes: &HashSet<String>) -> Vec<String> {
+        let mut visited = vec![start.to_owned()];
+        let mut seen: HashSet<&str> = HashSet::new();
+        seen.insert(start);
+        let mut i = 0;
+        while i < visited.len() {
+            let mut next: Vec<_> = self.nodes[&visited[i]]
+                .next
+                .iter()
+                .filter(|nx| nodes.contains(*nx))
+                .collect();
+            next.sort();
+            for nx in next {
+                if seen.insert(nx) {
+                    visited.push(nx.clone());
+                }
+            }
+            i += 1;
+        }
+        visited
+    }
+
+    /// Blocks in `region` which are the function entry or have a predecessor outside of `region`,
+    /// in reverse postorder.
+    fn region_entries(&self, region: &HashSet<String>, reachable: &HashSet<String>) -> Vec<String> {
+        self.reverse_postorder()
+            .into_iter()
+            .filter(|b| region.contains(b))
+            .filter(|b| {
+                *b == self.entry
+                    || self.nodes[b]
+                        .prev
+                        .iter()
+                        .any(|p| !region.contains(p) && reachable.contains(p))
+            })
+            .collect()
+    }
+
+    fn find_irreducible(
+        &self,
+        nodes: &HashSet<String>,
+        reachable: &HashSet<String>,
+        regions: &mut Vec<Vec<String>>,
+    ) {
+        for mut scc in self.strongly_connected_components(nodes) {
+            let cyclic = scc.len() > 1 || self.nodes[&scc[0]].next.contains(&scc[0]);
+            if !cyclic {
+                continue;
+            }
+            let scc_set: HashSet<String> = scc.iter().cloned().collect();
+            let entries = self.region_entries(&scc_set, reachable);
+            if entries.len() > 1 {
+                scc.sort();
+                regions.push(scc);
+            } else {
+                // a loop with a single header: look for irreducible regions inside of it
+                let inner = scc_set.into_iter().filter(|b| *b != entries[0]).collect();
+                self.find_irreducible(&inner, reachable, regions);
+            }
+        }
+    }
+
+    /// SCCs of the subgraph induced by `nodes` (Kosaraju's algorithm), in topological order
+    fn strongly_connected_components(&self, nodes: &HashSet<String>) -> Vec<Vec<String>> {
+        let succ = |b: &str| -> Vec<&String> {
+            let mut next: Vec<_> = self.nodes[b]
+                .next
+                .iter()
+                .filter(|nx| nodes.contains(*nx))
+                .collect();
+            next.sort_unstable_by(|a, b| b.cmp(a));
+            next
+        };
+
+        let mut finished = Vec::new();
+        let mut visited: HashSet<&String> = HashSet::new();
+        for root in self
+            .reverse_postorder()
+            .iter()
+            .filter(|b| nodes.contains(*b))
+        {
+            let root = nodes.get(root).unwrap();
+            if !visited.insert(root) {
+                continue;
+            }
+            let mut stack = vec![(root, succ(root))];
+            while let Some((b, next)) = stack.last_mut() {
+                match next.pop() {
+                    Some(nx) => {
+                        if visited.insert(nx) {
+                            stack.push((nx, succ(nx)));
+                        }
+                    }
+                    None => {
+                        finished.push(*b);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        let mut sccs = Vec::new();
+        let mut assigned: HashSet<&String> = HashSet::new();
+        for &root in finished.iter().rev() {
+            if !assigned.insert(root) {
+                continue;
+            }
+            let mut scc = Vec::new();
+            let mut stack = vec![root];
+            while let Some(b) = stack.pop() {
+                scc.push(b.clone());
+                for p in self.nodes[b].prev.iter().filter(|p| nodes.contains(*p)) {
+                    if assigned.insert(p) {
+                        stack.push(p);
+                    }
+                }
+            }
+            sccs.push(scc);
+        }
+        sccs
+    }
+}
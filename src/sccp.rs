@@ -0,0 +1,268 @@
+use crate::cfg::{Cfg, EdgeKind, InstructionId};
+use crate::data_flow_framework::{typed_literal, ConstValue};
+use crate::lattice::Lattice;
+use crate::ssa::UNDEFINED;
+use bril_rs::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Sparse conditional constant propagation (Wegman and Zadeck) on a function in SSA form.
+///
+/// Values only move down from `Top` and edges only become executable,
+/// so a block is reached only if some path of executable edges leads to it.
+struct Sccp<'a> {
+    cfg: &'a Cfg,
+    values: HashMap<String, ConstValue>,
+    /// variable --> instructions using it
+    uses: HashMap<String, Vec<InstructionId>>,
+    executable_edges: HashSet<(String, String)>,
+    executable_blocks: HashSet<String>,
+    flow_worklist: VecDeque<(String, String)>,
+    ssa_worklist: VecDeque<InstructionId>,
+}
+
+impl<'a> Sccp<'a> {
+    fn run(cfg: &'a Cfg, args: &[Argument]) -> Self {
+        let mut uses: HashMap<String, Vec<InstructionId>> = HashMap::new();
+        for (name, node) in cfg.nodes.iter() {
+            for (i, code) in node.block.iter().enumerate() {
+                if let Code::Instruction(Instruction::Value { args, .. })
+                | Code::Instruction(Instruction::Effect { args, .. }) = code
+                {
+                    for a in args {
+                        uses.entry(a.clone())
+                            .or_default()
+                            .push(InstructionId::new(name.clone(), i));
+                    }
+                }
+            }
+        }
+
+        let mut sccp = Self {
+            cfg,
+            values: args
+                .iter()
+                .map(|a| (a.name.clone(), ConstValue::Bottom))
+                .collect(),
+            uses,
+            executable_edges: HashSet::new(),
+            executable_blocks: HashSet::new(),
+            flow_worklist: VecDeque::new(),
+            ssa_worklist: VecDeque::new(),
+        };
+
+        sccp.visit_block(&cfg.entry);
+        loop {
+            if let Some((from, to)) = sccp.flow_worklist.pop_front() {
+                if !sccp.executable_edges.insert((from, to.clone())) {
+                    continue;
+                }
+                if sccp.executable_blocks.contains(&to) {
+                    // only the phis see the new edge
+                    for (i, code) in cfg.nodes[&to].block.iter().enumerate() {
+                        if is_phi(code) {
+                            sccp.visit(&InstructionId::new(to.clone(), i));
+                        }
+                    }
+                } else {
+                    sccp.visit_block(&to);
+                }
+            } else if let Some(id) = sccp.ssa_worklist.pop_front() {
+                if sccp.executable_blocks.contains(id.block()) {
+                    sccp.visit(&id);
+                }
+            } else {
+                break;
+            }
+        }
+        sccp
+    }
+
+    fn value(&self, var: &str) -> ConstValue {
+        self.values.get(var).cloned().unwrap_or(ConstValue::Top)
+    }
+
+    fn visit_block(&mut self, name: &str) {
+        self.executable_blocks.insert(name.to_owned());
+        let cfg = self.cfg;
+        let node = &cfg.nodes[name];
+        for i in 0..node.block.len() {
+            self.visit(&InstructionId::new(name.to_owned(), i));
+        }
+        // the successors of a block ending with `br` are added when visiting the `br`
+        if !matches!(
+            node.block.last(),
+            Some(Code::Instruction(Instruction::Effect {
+                op: EffectOps::Branch,
+                ..
+            }))
+        ) {
+            for (nx, _) in node.edges() {
+                self.flow_worklist
+                    .push_back((name.to_owned(), nx.to_owned()));
+            }
+        }
+    }
+
+    fn visit(&mut self, id: &InstructionId) {
+        let cfg = self.cfg;
+        let node = &cfg.nodes[id.block()];
+        let ins = match &node.block[id.index()] {
+            Code::Instruction(ins) => ins,
+            Code::Label { .. } => return,
+        };
+        match ins {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value = ConstValue::Const(typed_literal(const_type, value));
+                self.update(dest, value);
+            }
+            Instruction::Value {
+                op: ValueOps::Phi,
+                dest,
+                args,
+                labels,
+                ..
+            } => {
+                // arguments on edges which are not executable (yet) are ignored
+                let vals: Vec<_> = args
+                    .iter()
+                    .zip(labels.iter())
+                    .filter(|(a, l)| {
+                        *a != UNDEFINED
+                            && self
+                                .executable_edges
+                                .contains(&((*l).clone(), id.block().to_owned()))
+                    })
+                    .map(|(a, _)| self.value(a))
+                    .collect();
+                self.update(dest, ConstValue::eval(ValueOps::Phi, &vals));
+            }
+            Instruction::Value { op, dest, args, .. } => {
+                let vals: Vec<_> = args.iter().map(|a| self.value(a)).collect();
+                self.update(dest, ConstValue::eval(*op, &vals));
+            }
+            Instruction::Effect {
+                op: EffectOps::Branch,
+                args,
+                ..
+            } => {
+                let cond = self.value(&args[0]);
+                for (nx, kind) in node.edges() {
+                    let feasible = match (&cond, kind) {
+                        (ConstValue::Bottom, _) => true,
+                        (ConstValue::Const(Literal::Bool(b)), EdgeKind::BranchTrue) => *b,
+                        (ConstValue::Const(Literal::Bool(b)), EdgeKind::BranchFalse) => !*b,
+                        _ => false,
+                    };
+                    if feasible {
+                        self.flow_worklist
+                            .push_back((id.block().to_owned(), nx.to_owned()));
+                    }
+                }
+            }
+            Instruction::Effect { .. } => {}
+        }
+    }
+
+    fn update(&mut self, var: &str, value: ConstValue) {
+        let old = self.value(var);
+        let new = old.meet(&value);
+        if new != old {
+            self.values.insert(var.to_owned(), new);
+            if let Some(uses) = self.uses.get(var) {
+                self.ssa_worklist.extend(uses.iter().cloned());
+            }
+        }
+    }
+}
+
+fn is_phi(code: &Code) -> bool {
+    matches!(
+        code,
+        Code::Instruction(Instruction::Value {
+            op: ValueOps::Phi,
+            ..
+        })
+    )
+}
+
+impl Cfg {
+    /// Sparse conditional constant propagation on a function in SSA form.
+    ///
+    /// Instructions computing a constant are replaced by `const`, a `br` on a constant
+    /// becomes a `jmp`, and blocks which are no longer reachable are deleted.
+    pub fn sccp(&mut self, args: &[Argument]) {
+        let (values, executable_edges, executable_blocks) = {
+            let sccp = Sccp::run(self, args);
+            (sccp.values, sccp.executable_edges, sccp.executable_blocks)
+        };
+
+        for (name, node) in self.nodes.iter_mut() {
+            // blocks which never run are left alone, unreachable ones are deleted below
+            if !executable_blocks.contains(name) {
+                continue;
+            }
+            let mut phis = Vec::new();
+            let mut consts = Vec::new();
+            let mut rest = Vec::new();
+            for code in node.block.drain(..) {
+                let constant = match &code {
+                    Code::Instruction(Instruction::Value { dest, op_type, .. }) => {
+                        match values.get(dest) {
+                            Some(ConstValue::Const(lit)) => {
+                                Some(Code::Instruction(Instruction::Constant {
+                                    op: ConstOps::Const,
+                                    dest: dest.clone(),
+                                    const_type: op_type.clone(),
+                                    value: lit.clone(),
+                                }))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                match constant {
+                    // keep phis at the head of the block
+                    Some(c) if is_phi(&code) => consts.push(c),
+                    Some(c) => rest.push(c),
+                    None if is_phi(&code) => phis.push(code),
+                    None => rest.push(code),
+                }
+            }
+            node.block = phis;
+            node.block.extend(consts);
+            node.block.extend(rest);
+
+            // fold branches of which only one side is taken
+            if let Some(Code::Instruction(Instruction::Effect {
+                op: EffectOps::Branch,
+                args,
+                ..
+            })) = node.block.last()
+            {
+                if let Some(ConstValue::Const(Literal::Bool(b))) = values.get(&args[0]) {
+                    let target = node.branch_target(*b).unwrap().to_owned();
+                    debug_assert!(executable_edges.contains(&(name.clone(), target.clone())));
+                    *node.block.last_mut().unwrap() = Code::Instruction(Instruction::Effect {
+                        op: EffectOps::Jump,
+                        args: Vec::new(),
+                        funcs: Vec::new(),
+                        labels: vec![target.clone()],
+                    });
+                    node.next = vec![target];
+                }
+            }
+        }
+
+        self.refresh_prev();
+        self.remove_unreachable();
+        for node in self.nodes.values_mut() {
+            node.prune_phi_labels();
+        }
+    }
+}
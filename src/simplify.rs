@@ -0,0 +1,137 @@
+use crate::cfg::Cfg;
+use bril_rs::*;
+
+impl Cfg {
+    /// Clean up the graph until nothing changes:
+    /// remove unreachable blocks, fold `br` with identical targets into `jmp`,
+    /// bypass empty forwarding blocks and merge straight-line blocks.
+    pub fn simplify(&mut self) {
+        loop {
+            let mut changed = self.remove_unreachable();
+            changed |= self.fold_branches();
+            changed |= self.remove_forwarding_blocks();
+            changed |= self.merge_blocks();
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Delete the blocks which cannot be reached from `entry`.
+    /// Phis no longer refer to the deleted blocks.
+    pub fn remove_unreachable(&mut self) -> bool {
+        let reachable = self.reachable();
+        if reachable.len() == self.nodes.len() {
+            return false;
+        }
+        self.nodes.retain(|name, _| reachable.contains(name));
+        self.order.retain(|name| reachable.contains(name));
+        self.refresh_prev();
+        for node in self.nodes.values_mut() {
+            node.prune_phi_labels();
+        }
+        true
+    }
+
+    /// Replace `br c .l .l` by `jmp .l`.
+    pub fn fold_branches(&mut self) -> bool {
+        let mut changed = false;
+        for node in self.nodes.values_mut() {
+            if let Some(Code::Instruction(Instruction::Effect {
+                op: op @ EffectOps::Branch,
+                args,
+                labels,
+                ..
+            })) = node.block.last_mut()
+            {
+                if labels.len() == 2 && labels[0] == labels[1] {
+                    *op = EffectOps::Jump;
+                    args.clear();
+                    labels.pop();
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Send the predecessors of a block which only passes control on (it is empty or a lone `jmp`)
+    /// directly to its successor.
+    ///
+    /// NOTE: the entry and blocks whose successor starts with phis are kept.
+    pub fn remove_forwarding_blocks(&mut self) -> bool {
+        let mut changed = false;
+        for name in self.order.clone() {
+            let node = &self.nodes[&name];
+            let only_jumps = node.block.iter().all(|code| {
+                matches!(
+                    code,
+                    Code::Instruction(Instruction::Effect {
+                        op: EffectOps::Jump,
+                        ..
+                    })
+                )
+            });
+            if name == self.entry || !only_jumps || node.next.len() != 1 {
+                continue;
+            }
+            let target = node.next[0].clone();
+            if target == name || self.nodes[&target].has_phi() {
+                continue;
+            }
+
+            let mut preds: Vec<String> = node.prev.iter().cloned().collect();
+            preds.sort();
+            for p in preds {
+                self.nodes.get_mut(&p).unwrap().retarget(&name, &target);
+            }
+            self.nodes.remove(&name);
+            self.order.retain(|b| *b != name);
+            self.refresh_prev();
+            changed = true;
+        }
+        changed
+    }
+
+    /// Append a block to its only predecessor if it is that predecessor's only successor.
+    ///
+    /// NOTE: blocks starting with phis are kept.
+    pub fn merge_blocks(&mut self) -> bool {
+        let mut changed = false;
+        for name in self.order.clone() {
+            let node = match self.nodes.get(&name) {
+                Some(node) => node,
+                None => continue,
+            };
+            if name == self.entry || node.prev.len() != 1 || node.has_phi() {
+                continue;
+            }
+            let pred = node.prev.iter().next().unwrap().clone();
+            // a label elsewhere in the predecessor (e.g. of `guard`) still needs the block
+            let p = &self.nodes[&pred];
+            if pred == name || p.next != [name.clone()] || p.edges().len() != 1 {
+                continue;
+            }
+
+            let node = self.nodes.remove(&name).unwrap();
+            self.order.retain(|b| *b != name);
+            for nx in node.next.iter() {
+                self.nodes
+                    .get_mut(nx)
+                    .unwrap()
+                    .rename_phi_label(&name, &pred);
+            }
+            let p = self.nodes.get_mut(&pred).unwrap();
+            // the only jump left is to the merged block
+            if p.is_terminated() {
+                p.block.pop();
+            }
+            p.block.extend(node.block);
+            p.next = node.next;
+            p.fallthrough = node.fallthrough;
+            self.refresh_prev();
+            changed = true;
+        }
+        changed
+    }
+}
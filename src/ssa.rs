@@ -0,0 +1,395 @@
+use crate::cfg::Cfg;
+use crate::data_flow_framework::{DataFlowAnalysis, LiveVariables};
+use crate::dominance::DominatorTree;
+use crate::lattice::{Lattice, MaySet};
+use bril_rs::*;
+use std::collections::{HashMap, HashSet};
+
+/// The phi argument for a path on which the variable has no definition
+pub const UNDEFINED: &str = "__undefined";
+
+/// Which phis `Cfg::to_ssa` places
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsaMode {
+    /// at the iterated dominance frontiers of the definitions of every variable
+    Minimal,
+    /// as `Minimal`, but only for variables which are used in a block before being defined there
+    SemiPruned,
+    /// as `Minimal`, but only where the variable is live
+    Pruned,
+}
+
+impl Cfg {
+    /// Convert the function into SSA form.
+    ///
+    /// Phis are placed at the iterated dominance frontiers of the definitions of each variable,
+    /// then every definition is renamed to `<var>.<n>` in a walk over the dominator tree.
+    /// Unreachable blocks are removed first, and an entry block is added if needed,
+    /// so that the entry never needs phis.
+    ///
+    /// Returns the number of phis placed.
+    pub fn to_ssa(&mut self, args: &[Argument], mode: SsaMode) -> usize {
+        self.remove_unreachable();
+        self.add_entry_block();
+
+        let types = variable_types(self, args);
+        let tree = DominatorTree::new(self);
+        let phis = place_phis(self, &tree, args, &types, mode);
+
+        let mut renamer = Renamer {
+            stacks: args
+                .iter()
+                .map(|a| (a.name.clone(), vec![a.name.clone()]))
+                .collect(),
+            counters: HashMap::new(),
+            used: types.keys().cloned().collect(),
+        };
+        renamer.rename(self, &tree, &phis, &tree.root);
+        phis.values().map(|vars| vars.len()).sum()
+    }
+
+    /// Replace the phis by copies at the end of the predecessors.
+    ///
+    /// Edges from blocks with several successors are split where copies are needed,
+    /// so that the copies only run on the edge they belong to. The copies of each edge happen in parallel;
+    /// they are ordered so that no value is overwritten before it is read, and a cycle
+    /// of copies is broken with a temporary of their type.
+    pub fn from_ssa(&mut self) {
+        let mut names: Vec<String> = self.order.clone();
+        names.retain(|b| self.nodes[b].has_phi());
+
+        // a cycle of copies is broken with a temporary of the type of its variables
+        let mut types: Vec<Type> = Vec::new();
+        for code in names.iter().flat_map(|b| self.nodes[b].block.iter()) {
+            if let Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                op_type,
+                ..
+            }) = code
+            {
+                if !types.contains(op_type) {
+                    types.push(op_type.clone());
+                }
+            }
+        }
+        let tmp_names = self.fresh_vars("__tmp", types.len());
+        let temps: Vec<(Type, String)> = types.into_iter().zip(tmp_names).collect();
+
+        for b in names {
+            let mut preds: Vec<String> = self.nodes[&b].prev.iter().cloned().collect();
+            preds.sort();
+            for p in preds {
+                let copies: Vec<Copy> = self.nodes[&b]
+                    .block
+                    .iter()
+                    .filter_map(|code| match code {
+                        Code::Instruction(Instruction::Value {
+                            op: ValueOps::Phi,
+                            dest,
+                            op_type,
+                            args,
+                            labels,
+                            ..
+                        }) => {
+                            let i = labels.iter().position(|l| *l == p)?;
+                            Some(Copy {
+                                dest: dest.clone(),
+                                src: args[i].clone(),
+                                ty: op_type.clone(),
+                            })
+                        }
+                        _ => None,
+                    })
+                    .filter(|c| c.src != UNDEFINED && c.src != c.dest)
+                    .collect();
+                if copies.is_empty() {
+                    continue;
+                }
+
+                let p = if self.nodes[&p].next.len() > 1 {
+                    self.split_edge(&p, &b)
+                } else {
+                    p
+                };
+                let node = self.nodes.get_mut(&p).unwrap();
+                let at = if node.is_terminated() {
+                    node.block.len() - 1
+                } else {
+                    node.block.len()
+                };
+                node.block
+                    .splice(at..at, sequentialize(copies, &temps).into_iter().map(id));
+            }
+        }
+
+        for node in self.nodes.values_mut() {
+            node.block.retain(|code| {
+                !matches!(
+                    code,
+                    Code::Instruction(Instruction::Value {
+                        op: ValueOps::Phi,
+                        ..
+                    })
+                )
+            });
+        }
+    }
+}
+
+/// `dest: ty = id src`
+struct Copy {
+    dest: String,
+    src: String,
+    ty: Type,
+}
+
+fn id(c: Copy) -> Code {
+    Code::Instruction(Instruction::Value {
+        op: ValueOps::Id,
+        dest: c.dest,
+        op_type: c.ty,
+        args: vec![c.src],
+        funcs: Vec::new(),
+        labels: Vec::new(),
+    })
+}
+
+/// Order parallel copies (with distinct destinations) so that they can run one after another.
+/// `temps` holds the temporary to use for each type.
+fn sequentialize(mut pending: Vec<Copy>, temps: &[(Type, String)]) -> Vec<Copy> {
+    let mut seq = Vec::new();
+    while !pending.is_empty() {
+        // a copy is safe once no other copy reads its destination
+        let ready = pending
+            .iter()
+            .position(|c| pending.iter().all(|other| other.src != c.dest));
+        match ready {
+            Some(i) => seq.push(pending.remove(i)),
+            None => {
+                // every destination is still to be read: the copies form cycles.
+                // save one destination so that it can be overwritten.
+                let c = &pending[0];
+                let tmp = &temps.iter().find(|(ty, _)| *ty == c.ty).unwrap().1;
+                let save = Copy {
+                    dest: tmp.clone(),
+                    src: c.dest.clone(),
+                    ty: c.ty.clone(),
+                };
+                for other in pending.iter_mut().filter(|other| other.src == save.src) {
+                    other.src = tmp.clone();
+                }
+                seq.push(save);
+            }
+        }
+    }
+    seq
+}
+
+fn variable_types(cfg: &Cfg, args: &[Argument]) -> HashMap<String, Type> {
+    let mut types: HashMap<String, Type> = args
+        .iter()
+        .map(|a| (a.name.clone(), a.arg_type.clone()))
+        .collect();
+    for code in cfg.nodes.values().flat_map(|node| node.block.iter()) {
+        match code {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => {
+                types
+                    .entry(dest.clone())
+                    .or_insert_with(|| const_type.clone());
+            }
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => {
+                types.entry(dest.clone()).or_insert_with(|| op_type.clone());
+            }
+            _ => {}
+        }
+    }
+    types
+}
+
+fn dest(code: &Code) -> Option<&String> {
+    match code {
+        Code::Instruction(Instruction::Constant { dest, .. })
+        | Code::Instruction(Instruction::Value { dest, .. }) => Some(dest),
+        _ => None,
+    }
+}
+
+/// Variables which are used in some block before they are defined in it
+fn non_local_variables(cfg: &Cfg) -> HashSet<&String> {
+    let mut non_local = HashSet::new();
+    for node in cfg.nodes.values() {
+        let mut defined = HashSet::new();
+        for code in node.block.iter() {
+            if let Code::Instruction(Instruction::Value { args, .. })
+            | Code::Instruction(Instruction::Effect { args, .. }) = code
+            {
+                non_local.extend(args.iter().filter(|a| !defined.contains(a)));
+            }
+            defined.extend(dest(code));
+        }
+    }
+    non_local
+}
+
+/// Insert empty phis at the head of blocks.
+/// Returns block --> the variables of its phis, in order.
+fn place_phis(
+    cfg: &mut Cfg,
+    tree: &DominatorTree,
+    args: &[Argument],
+    types: &HashMap<String, Type>,
+    mode: SsaMode,
+) -> HashMap<String, Vec<String>> {
+    let frontiers = tree.frontiers(cfg);
+    let live = match mode {
+        SsaMode::Pruned => Some(LiveVariables::drive(cfg, MaySet::top())),
+        _ => None,
+    };
+    let non_local = match mode {
+        SsaMode::SemiPruned => Some(non_local_variables(cfg)),
+        _ => None,
+    };
+
+    // variable --> blocks defining it
+    let mut defs: HashMap<&String, HashSet<&String>> = HashMap::new();
+    for a in args {
+        defs.entry(&a.name).or_default().insert(&cfg.entry);
+    }
+    for (name, node) in cfg.nodes.iter() {
+        for d in node.block.iter().filter_map(dest) {
+            defs.entry(d).or_default().insert(name);
+        }
+    }
+    let mut vars: Vec<&String> = defs.keys().copied().collect();
+    vars.sort();
+
+    let mut phis: HashMap<String, Vec<String>> = HashMap::new();
+    for var in vars {
+        if matches!(&non_local, Some(non_local) if !non_local.contains(var)) {
+            continue;
+        }
+        let mut worklist: Vec<&String> = defs[var].iter().copied().collect();
+        let mut visited: HashSet<&String> = HashSet::new();
+        while let Some(b) = worklist.pop() {
+            for f in frontiers.get(b).into_iter().flatten() {
+                if !visited.insert(f) {
+                    continue;
+                }
+                let needed = match &live {
+                    Some(live) => live.get(f).unwrap().0.contains(var),
+                    None => true,
+                };
+                if needed {
+                    phis.entry(f.clone()).or_default().push(var.clone());
+                    worklist.push(f);
+                }
+            }
+        }
+    }
+
+    for (b, vars) in phis.iter() {
+        let node = cfg.nodes.get_mut(b).unwrap();
+        let mut labels: Vec<String> = node.prev.iter().cloned().collect();
+        labels.sort();
+        let head = vars.iter().map(|var| {
+            Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                dest: var.clone(),
+                op_type: types[var].clone(),
+                args: vec![UNDEFINED.to_owned(); labels.len()],
+                funcs: Vec::new(),
+                labels: labels.clone(),
+            })
+        });
+        node.block.splice(0..0, head);
+    }
+    phis
+}
+
+struct Renamer {
+    /// original name --> names of the reaching definitions
+    stacks: HashMap<String, Vec<String>>,
+    counters: HashMap<String, usize>,
+    /// every name in the function, so that new names do not clash
+    used: HashSet<String>,
+}
+
+impl Renamer {
+    fn fresh(&mut self, var: &str) -> String {
+        let counter = self.counters.entry(var.to_owned()).or_insert(0);
+        let name = loop {
+            let name = format!("{}.{}", var, counter);
+            *counter += 1;
+            if !self.used.contains(&name) {
+                break name;
+            }
+        };
+        self.used.insert(name.clone());
+        self.stacks
+            .entry(var.to_owned())
+            .or_default()
+            .push(name.clone());
+        name
+    }
+
+    fn current(&self, var: &str) -> Option<&String> {
+        self.stacks.get(var).and_then(|s| s.last())
+    }
+
+    fn rename(
+        &mut self,
+        cfg: &mut Cfg,
+        tree: &DominatorTree,
+        phis: &HashMap<String, Vec<String>>,
+        b: &str,
+    ) {
+        let phi_vars = phis.get(b).map(|v| v.as_slice()).unwrap_or(&[]);
+        let mut defined: Vec<String> = Vec::new();
+        let node = cfg.nodes.get_mut(b).unwrap();
+        for (i, code) in node.block.iter_mut().enumerate() {
+            let ins = match code {
+                Code::Instruction(ins) => ins,
+                Code::Label { .. } => continue,
+            };
+            // the arguments of our phis are filled in by the predecessors
+            if i >= phi_vars.len() {
+                if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } = ins {
+                    for a in args.iter_mut() {
+                        if let Some(cur) = self.current(a) {
+                            *a = cur.clone();
+                        }
+                    }
+                }
+            }
+            if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = ins {
+                defined.push(dest.clone());
+                *dest = self.fresh(dest);
+            }
+        }
+
+        for s in node.next.clone() {
+            for (i, var) in phis.get(&s).into_iter().flatten().enumerate() {
+                let cur = match self.current(var) {
+                    Some(cur) => cur.clone(),
+                    None => continue,
+                };
+                if let Some(Code::Instruction(Instruction::Value { args, labels, .. })) =
+                    cfg.nodes.get_mut(&s).unwrap().block.get_mut(i)
+                {
+                    if let Some(j) = labels.iter().position(|l| l == b) {
+                        args[j] = cur;
+                    }
+                }
+            }
+        }
+
+        for c in tree.children(b) {
+            self.rename(cfg, tree, phis, c);
+        }
+        for var in defined {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+}
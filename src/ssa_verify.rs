@@ -0,0 +1,152 @@
+use crate::cfg::{Cfg, InstructionId};
+use crate::dominance::Dominators;
+use crate::ssa::UNDEFINED;
+use bril_rs::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A violation of the SSA form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SsaError {
+    /// `var` was already defined (or is an argument) when `id` defines it again
+    Redefinition { var: String, id: InstructionId },
+    /// no definition of `var` dominates its use at `id`
+    NotDominated { var: String, id: InstructionId },
+    /// a phi follows an instruction which is not a phi
+    PhiNotAtHead(InstructionId),
+    /// the labels of the phi at `id` are not the predecessors of its block
+    PhiLabels {
+        id: InstructionId,
+        labels: Vec<String>,
+        preds: Vec<String>,
+    },
+}
+
+impl fmt::Display for SsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SsaError::Redefinition { var, id } => write!(f, "`{}` is defined again at {}", var, id),
+            SsaError::NotDominated { var, id } => {
+                write!(
+                    f,
+                    "use of `{}` at {} is not dominated by its definition",
+                    var, id
+                )
+            }
+            SsaError::PhiNotAtHead(id) => {
+                write!(f, "phi at {} is not at the head of its block", id)
+            }
+            SsaError::PhiLabels { id, labels, preds } => write!(
+                f,
+                "phi at {} has labels [{}] but the predecessors are [{}]",
+                id,
+                labels.join(", "),
+                preds.join(", ")
+            ),
+        }
+    }
+}
+
+/// Check that the function is in SSA form:
+/// - every variable is defined exactly once (arguments count as definitions),
+/// - every use is dominated by the definition of the variable,
+///   where a phi uses its argument at the end of the corresponding predecessor,
+/// - phis only appear at the head of a block,
+/// - the labels of each phi are exactly the predecessors of its block.
+///
+/// Uses in unreachable blocks are not checked. Errors are reported in the order of blocks.
+pub fn verify(cfg: &Cfg, args: &[Argument]) -> Vec<SsaError> {
+    let mut errors = Vec::new();
+
+    // variable --> its definition, or `None` for arguments
+    let mut defs: HashMap<&String, Option<InstructionId>> =
+        args.iter().map(|a| (&a.name, None)).collect();
+    for name in cfg.order.iter() {
+        for (i, code) in cfg.nodes[name].block.iter().enumerate() {
+            if let Code::Instruction(Instruction::Constant { dest, .. })
+            | Code::Instruction(Instruction::Value { dest, .. }) = code
+            {
+                let id = InstructionId::new(name.clone(), i);
+                if defs.contains_key(dest) {
+                    errors.push(SsaError::Redefinition {
+                        var: dest.clone(),
+                        id,
+                    });
+                } else {
+                    defs.insert(dest, Some(id));
+                }
+            }
+        }
+    }
+
+    let dom = Dominators::compute(cfg);
+    // whether the definition of `var` is available at the end of `block`,
+    // or before its instruction `index`
+    let available = |var: &String, block: &str, index: Option<usize>| match defs.get(var) {
+        Some(None) => true,
+        Some(Some(def)) if def.block() == block => match index {
+            Some(i) => def.index() < i,
+            None => true,
+        },
+        Some(Some(def)) => dom.dominates(def.block(), block),
+        None => false,
+    };
+
+    for name in cfg.order.iter() {
+        let node = &cfg.nodes[name];
+        let mut preds: Vec<String> = node.prev.iter().cloned().collect();
+        preds.sort();
+        let mut at_head = true;
+        for (i, code) in node.block.iter().enumerate() {
+            let id = InstructionId::new(name.clone(), i);
+            match code {
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Phi,
+                    args,
+                    labels,
+                    ..
+                }) => {
+                    if !at_head {
+                        errors.push(SsaError::PhiNotAtHead(id.clone()));
+                    }
+                    let mut sorted = labels.clone();
+                    sorted.sort();
+                    if sorted != preds {
+                        errors.push(SsaError::PhiLabels {
+                            id: id.clone(),
+                            labels: labels.clone(),
+                            preds: preds.clone(),
+                        });
+                    }
+                    for (arg, label) in args.iter().zip(labels.iter()) {
+                        if arg == UNDEFINED || !dom.is_reachable(label) {
+                            continue;
+                        }
+                        if !available(arg, label, None) {
+                            errors.push(SsaError::NotDominated {
+                                var: arg.clone(),
+                                id: id.clone(),
+                            });
+                        }
+                    }
+                }
+                Code::Instruction(Instruction::Value { args, .. })
+                | Code::Instruction(Instruction::Effect { args, .. }) => {
+                    at_head = false;
+                    if !dom.is_reachable(name) {
+                        continue;
+                    }
+                    for arg in args.iter().filter(|arg| !available(arg, name, Some(i))) {
+                        errors.push(SsaError::NotDominated {
+                            var: arg.clone(),
+                            id: id.clone(),
+                        });
+                    }
+                }
+                Code::Instruction(Instruction::Constant { .. }) => at_head = false,
+                Code::Label { .. } => {}
+            }
+        }
+    }
+    errors
+}